@@ -0,0 +1,60 @@
+use std::time::{Duration, Instant};
+
+use crate::compression::{TexFormat, TexLayout};
+use crate::reerr::Result;
+
+const FORMATS: [&str; 4] = ["BC1_UNORM", "BC3_UNORM", "BC5_UNORM", "BC7_UNORM"];
+
+// xorshift, the same blocks every run so timings can be compared
+fn synthetic_blocks(len: usize) -> Vec<u8> {
+    let mut state = 0x2545F4914F6CDD1Du64;
+    (0..len.div_ceil(8))
+        .flat_map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state.to_le_bytes()
+        })
+        .take(len)
+        .collect()
+}
+
+// best of iterations, with the last result
+fn time<T>(iterations: usize, mut f: impl FnMut() -> T) -> (Duration, T) {
+    let mut best = Duration::MAX;
+    let mut result = None;
+    for _ in 0..iterations.max(1) {
+        let start = Instant::now();
+        result = Some(std::hint::black_box(f()));
+        best = best.min(start.elapsed());
+    }
+    (best, result.unwrap())
+}
+
+// Times the per texel closure decoder against the row decoder on one and on threads threads, and
+// checks they all decode the same texels. Run with `cargo run --release -- bench-tex`
+pub fn bench_decoders(size: usize, iterations: usize, threads: usize) -> Result<()> {
+    for name in FORMATS {
+        let format = TexFormat::from_name(name).unwrap();
+        let data = synthetic_blocks(format.row_len(size) * size.div_ceil(format.kind.cell().0));
+
+        let (closure_time, reference) = time(iterations, || {
+            let mut out = vec![[0u8; 4]; size * size];
+            format.kind.decode_image(&data, size, size, TexLayout::Linear, |x, y, v: [u8; 4]| out[y * size + x] = v);
+            out
+        });
+        let (rows_time, rows) = time(iterations, || format.kind.decode_linear::<[u8; 4]>(&data, size, size, 1));
+        let (threads_time, threaded) = time(iterations, || format.kind.decode_linear::<[u8; 4]>(&data, size, size, threads));
+        if rows != reference || threaded != reference {
+            return Err(format!("{name}: row decoding doesn't match the closure decoder").into())
+        }
+
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        let mpx = (size * size) as f64 / threads_time.as_secs_f64() / 1e6;
+        println!(
+            "{name} {size}x{size}: closure {:.1} ms, rows {:.1} ms, {threads} threads {:.1} ms ({mpx:.1} Mpx/s)",
+            ms(closure_time), ms(rows_time), ms(threads_time)
+        );
+    }
+    Ok(())
+}
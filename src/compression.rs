@@ -1,49 +1,244 @@
 use crate::bitfield::BitField;
 
-#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum CompressionType {
+    R32G32B32A32Float,
+    R32G32B32Float,
+    R16G16B16A16Float,
+    R32G32Float,
+    R10G10B10A2Unorm,
+    R11G11B10Float,
     R8G8B8A8Unorm,
+    R16G16Float,
+    R32Float,
     R8G8Unorm,
+    R16Float,
     R8Unorm,
     Bc1Unorm,
     Bc2Unorm,
     Bc3Unorm,
     Bc4Unorm,
     Bc5Unorm,
-    Bc6Unorm,
+    B8G8R8A8Unorm,
+    B8G8R8X8Unorm,
+    Bc6hUf16,
+    Bc6hSf16,
     Bc7Unorm,
-    Unknown
+}
+
+pub struct TexFormat {
+    // tex format codes are DXGI_FORMAT values
+    pub code: u32,
+    pub name: &'static str,
+    pub kind: CompressionType,
+    pub srgb: bool,
+}
+
+const fn format(code: u32, name: &'static str, kind: CompressionType, srgb: bool) -> TexFormat {
+    TexFormat { code, name, kind, srgb }
+}
+
+// Every format that can be decoded, everything else is unsupported
+#[rustfmt::skip]
+pub const TEX_FORMATS: &[TexFormat] = &[
+    format(0x02, "R32G32B32A32_FLOAT", CompressionType::R32G32B32A32Float, false),
+    format(0x06, "R32G32B32_FLOAT", CompressionType::R32G32B32Float, false),
+    format(0x0A, "R16G16B16A16_FLOAT", CompressionType::R16G16B16A16Float, false),
+    format(0x10, "R32G32_FLOAT", CompressionType::R32G32Float, false),
+    format(0x18, "R10G10B10A2_UNORM", CompressionType::R10G10B10A2Unorm, false),
+    format(0x1A, "R11G11B10_FLOAT", CompressionType::R11G11B10Float, false),
+    format(0x1C, "R8G8B8A8_UNORM", CompressionType::R8G8B8A8Unorm, false),
+    format(0x1D, "R8G8B8A8_UNORM_SRGB", CompressionType::R8G8B8A8Unorm, true),
+    format(0x22, "R16G16_FLOAT", CompressionType::R16G16Float, false),
+    format(0x29, "R32_FLOAT", CompressionType::R32Float, false),
+    format(0x31, "R8G8_UNORM", CompressionType::R8G8Unorm, false),
+    format(0x36, "R16_FLOAT", CompressionType::R16Float, false),
+    format(0x3D, "R8_UNORM", CompressionType::R8Unorm, false),
+    format(0x47, "BC1_UNORM", CompressionType::Bc1Unorm, false),
+    format(0x48, "BC1_UNORM_SRGB", CompressionType::Bc1Unorm, true),
+    format(0x4A, "BC2_UNORM", CompressionType::Bc2Unorm, false),
+    format(0x4B, "BC2_UNORM_SRGB", CompressionType::Bc2Unorm, true),
+    format(0x4D, "BC3_UNORM", CompressionType::Bc3Unorm, false),
+    format(0x4E, "BC3_UNORM_SRGB", CompressionType::Bc3Unorm, true),
+    format(0x50, "BC4_UNORM", CompressionType::Bc4Unorm, false),
+    format(0x53, "BC5_UNORM", CompressionType::Bc5Unorm, false),
+    format(0x57, "B8G8R8A8_UNORM", CompressionType::B8G8R8A8Unorm, false),
+    format(0x58, "B8G8R8X8_UNORM", CompressionType::B8G8R8X8Unorm, false),
+    format(0x5B, "B8G8R8A8_UNORM_SRGB", CompressionType::B8G8R8A8Unorm, true),
+    format(0x5D, "B8G8R8X8_UNORM_SRGB", CompressionType::B8G8R8X8Unorm, true),
+    format(0x5F, "BC6H_UF16", CompressionType::Bc6hUf16, false),
+    format(0x60, "BC6H_SF16", CompressionType::Bc6hSf16, false),
+    format(0x62, "BC7_UNORM", CompressionType::Bc7Unorm, false),
+    format(0x63, "BC7_UNORM_SRGB", CompressionType::Bc7Unorm, true),
+];
+
+impl TexFormat {
+    pub fn from_code(code: u32) -> Option<&'static TexFormat> {
+        TEX_FORMATS.iter().find(|format| format.code == code)
+    }
+
+    // the DXGI name, _UNORM can be left off
+    pub fn from_name(name: &str) -> Option<&'static TexFormat> {
+        TEX_FORMATS.iter().find(|format| {
+            format.name.eq_ignore_ascii_case(name) || format.name.eq_ignore_ascii_case(&format!("{name}_UNORM"))
+        })
+    }
+
+    // bytes in one row of cells
+    pub fn row_len(&self, width: usize) -> usize {
+        let (cell_size, cell_len) = self.kind.cell();
+        width.div_ceil(cell_size) * cell_len
+    }
+}
+
+// What a codec writes out, decoders convert to whichever the caller wants
+pub trait Texel: Copy {
+    fn from_unorm8(v: [u8; 4]) -> Self;
+    fn from_float(v: [f32; 4]) -> Self;
+}
+
+impl Texel for [u8; 4] {
+    fn from_unorm8(v: [u8; 4]) -> Self {
+        v
+    }
+
+    fn from_float(v: [f32; 4]) -> Self {
+        v.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+    }
+}
+
+impl Texel for [f32; 4] {
+    fn from_unorm8(v: [u8; 4]) -> Self {
+        v.map(|c| c as f32 / 255.0)
+    }
+
+    fn from_float(v: [f32; 4]) -> Self {
+        v
+    }
 }
 
 impl CompressionType {
+    // width/height and byte size of one cell, block compressed formats use 4x4 cells
+    pub fn cell(&self) -> (usize, usize) {
+        match self {
+            Self::R32G32B32A32Float => (1, 16),
+            Self::R32G32B32Float => (1, 12),
+            Self::R16G16B16A16Float | Self::R32G32Float => (1, 8),
+            Self::R10G10B10A2Unorm | Self::R11G11B10Float | Self::R8G8B8A8Unorm | Self::R16G16Float
+                | Self::R32Float | Self::B8G8R8A8Unorm | Self::B8G8R8X8Unorm => (1, 4),
+            Self::R8G8Unorm | Self::R16Float => (1, 2),
+            Self::R8Unorm => (1, 1),
+            Self::Bc1Unorm | Self::Bc4Unorm => (4, 8),
+            Self::Bc2Unorm | Self::Bc3Unorm | Self::Bc5Unorm | Self::Bc6hUf16 | Self::Bc6hSf16 | Self::Bc7Unorm => (4, 16),
+        }
+    }
+
+    pub fn is_block_compressed(&self) -> bool {
+        self.cell().0 > 1
+    }
 
-    pub fn get_bpps(format: u32) -> u32 {
-        let t = Self::get_type(format);
-        Self::bpps(t)
+    // formats that go past 0-1 and need exr/hdr to keep their range
+    pub fn is_float(&self) -> bool {
+        matches!(self,
+            Self::R32G32B32A32Float | Self::R32G32B32Float | Self::R16G16B16A16Float | Self::R32G32Float
+            | Self::R11G11B10Float | Self::R16G16Float | Self::R32Float | Self::R16Float
+            | Self::Bc6hUf16 | Self::Bc6hSf16)
     }
 
-    pub fn bpps(val: CompressionType) -> u32 {
-        match val {
-            Self::R8G8B8A8Unorm => 8,
-            Self::R8G8Unorm => 16,
-            Self::R8Unorm => 8,
-            Self::Bc1Unorm | Self::Bc4Unorm => 4,
-            Self::Bc2Unorm | Self::Bc3Unorm | Self::Bc5Unorm | Self::Bc6Unorm | Self::Bc7Unorm => 8,
-            Self::Unknown => 0,
+    pub fn decode_image<P: Texel, F: FnMut(usize, usize, P)>(
+        &self,
+        data: &[u8],
+        width: usize,
+        height: usize,
+        layout: TexLayout,
+        mut writer: F,
+    ) {
+        macro_rules! ldr {
+            ($codec:ty) => {
+                <$codec>::decode_image(data, width, height, layout, |x, y, v| writer(x, y, P::from_unorm8(v)))
+            };
+        }
+        macro_rules! hdr {
+            ($codec:ty) => {
+                <$codec>::decode_image(data, width, height, layout, |x, y, v| writer(x, y, P::from_float(v)))
+            };
+        }
+        match self {
+            Self::R32G32B32A32Float => hdr!(R32G32B32A32Float),
+            Self::R32G32B32Float => hdr!(R32G32B32Float),
+            Self::R16G16B16A16Float => hdr!(R16G16B16A16Float),
+            Self::R32G32Float => hdr!(R32G32Float),
+            Self::R10G10B10A2Unorm => hdr!(R10G10B10A2Unorm),
+            Self::R11G11B10Float => hdr!(R11G11B10Float),
+            Self::R8G8B8A8Unorm => ldr!(R8G8B8A8Unorm),
+            Self::R16G16Float => hdr!(R16G16Float),
+            Self::R32Float => hdr!(R32Float),
+            Self::R8G8Unorm => ldr!(R8G8Unorm),
+            Self::R16Float => hdr!(R16Float),
+            Self::R8Unorm => ldr!(R8Unorm),
+            Self::Bc1Unorm => ldr!(Bc1Unorm),
+            Self::Bc2Unorm => ldr!(Bc2Unorm),
+            Self::Bc3Unorm => ldr!(Bc3Unorm),
+            Self::Bc4Unorm => ldr!(Bc4Unorm),
+            Self::Bc5Unorm => ldr!(Bc5Unorm),
+            Self::B8G8R8A8Unorm => ldr!(B8G8R8A8Unorm),
+            Self::B8G8R8X8Unorm => ldr!(B8G8R8X8Unorm),
+            Self::Bc6hUf16 => hdr!(Bc6hUf16),
+            Self::Bc6hSf16 => hdr!(Bc6hSf16),
+            Self::Bc7Unorm => ldr!(Bc7Unorm),
         }
     }
-    pub fn get_type(format: u32) -> CompressionType {
-        match format {
-            0x1C | 0x1D => Self::R8G8B8A8Unorm,
-            0x31 => Self::R8G8Unorm,
-            0x3D => Self::R8Unorm,
-            0x47 | 0x48 => Self::Bc1Unorm,
-            0x4D | 0x4E => Self::Bc3Unorm,
-            0x50 => Self::Bc4Unorm,
-            0x53 => Self::Bc5Unorm,
-            0x62 | 0x63 => Self::Bc7Unorm,
-            _ => Self::Unknown
+
+    // Linear layout into a new width * height buffer, decoded on up to threads threads
+    pub fn decode_linear<P: Texel + Send>(&self, data: &[u8], width: usize, height: usize, threads: usize) -> Vec<P> {
+        let mut out = vec![P::from_unorm8([0; 4]); width * height];
+        macro_rules! ldr {
+            ($codec:ty) => {
+                <$codec>::decode_image_rows(data, width, height, threads, &mut out, P::from_unorm8)
+            };
+        }
+        macro_rules! hdr {
+            ($codec:ty) => {
+                <$codec>::decode_image_rows(data, width, height, threads, &mut out, P::from_float)
+            };
         }
+        match self {
+            Self::R32G32B32A32Float => hdr!(R32G32B32A32Float),
+            Self::R32G32B32Float => hdr!(R32G32B32Float),
+            Self::R16G16B16A16Float => hdr!(R16G16B16A16Float),
+            Self::R32G32Float => hdr!(R32G32Float),
+            Self::R10G10B10A2Unorm => hdr!(R10G10B10A2Unorm),
+            Self::R11G11B10Float => hdr!(R11G11B10Float),
+            Self::R8G8B8A8Unorm => ldr!(R8G8B8A8Unorm),
+            Self::R16G16Float => hdr!(R16G16Float),
+            Self::R32Float => hdr!(R32Float),
+            Self::R8G8Unorm => ldr!(R8G8Unorm),
+            Self::R16Float => hdr!(R16Float),
+            Self::R8Unorm => ldr!(R8Unorm),
+            Self::Bc1Unorm => ldr!(Bc1Unorm),
+            Self::Bc2Unorm => ldr!(Bc2Unorm),
+            Self::Bc3Unorm => ldr!(Bc3Unorm),
+            Self::Bc4Unorm => ldr!(Bc4Unorm),
+            Self::Bc5Unorm => ldr!(Bc5Unorm),
+            Self::B8G8R8A8Unorm => ldr!(B8G8R8A8Unorm),
+            Self::B8G8R8X8Unorm => ldr!(B8G8R8X8Unorm),
+            Self::Bc6hUf16 => hdr!(Bc6hUf16),
+            Self::Bc6hSf16 => hdr!(Bc6hSf16),
+            Self::Bc7Unorm => ldr!(Bc7Unorm),
+        }
+        out
+    }
+}
+
+pub fn decode_threads() -> usize {
+    std::thread::available_parallelism().map_or(1, |threads| threads.get())
+}
+
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
     }
 }
 
@@ -462,9 +657,188 @@ pub fn bc7_decompress_block<F: FnMut(usize, usize, [u8; 4])>(
     false
 }
 
-#[allow(dead_code)]
+// BC6H endpoints, field index is channel * 4 + endpoint
+const R0: u8 = 0; const R1: u8 = 1; const R2: u8 = 2; const R3: u8 = 3;
+const G0: u8 = 4; const G1: u8 = 5; const G2: u8 = 6; const G3: u8 = 7;
+const B0: u8 = 8; const B1: u8 = 9; const B2: u8 = 10; const B3: u8 = 11;
+
+struct Bc6hMode {
+    mode: u32,
+    transformed: bool,
+    // endpoint precision, then delta precision for r, g, b
+    bits: [u32; 4],
+    // (field, first bit, bit count) in the order they are stored after the mode bits
+    layout: &'static [(u8, u8, u8)],
+}
+
+#[rustfmt::skip]
+const BC6H_MODES: [Bc6hMode; 14] = [
+    Bc6hMode { mode: 0x00, transformed: true, bits: [10, 5, 5, 5], layout: &[
+        (G2,4,1),(B2,4,1),(B3,4,1),(R0,0,10),(G0,0,10),(B0,0,10),(R1,0,5),(G3,4,1),(G2,0,4),(G1,0,5),(B3,0,1),
+        (G3,0,4),(B1,0,5),(B3,1,1),(B2,0,4),(R2,0,5),(B3,2,1),(R3,0,5),(B3,3,1)] },
+    Bc6hMode { mode: 0x01, transformed: true, bits: [7, 6, 6, 6], layout: &[
+        (G2,5,1),(G3,4,1),(G3,5,1),(R0,0,7),(B3,0,1),(B3,1,1),(B2,4,1),(G0,0,7),(B2,5,1),(B3,2,1),(G2,4,1),
+        (B0,0,7),(B3,3,1),(B3,5,1),(B3,4,1),(R1,0,6),(G2,0,4),(G1,0,6),(G3,0,4),(B1,0,6),(B2,0,4),(R2,0,6),(R3,0,6)] },
+    Bc6hMode { mode: 0x02, transformed: true, bits: [11, 5, 4, 4], layout: &[
+        (R0,0,10),(G0,0,10),(B0,0,10),(R1,0,5),(R0,10,1),(G2,0,4),(G1,0,4),(G0,10,1),(B3,0,1),(G3,0,4),(B1,0,4),
+        (B0,10,1),(B3,1,1),(B2,0,4),(R2,0,5),(B3,2,1),(R3,0,5),(B3,3,1)] },
+    Bc6hMode { mode: 0x06, transformed: true, bits: [11, 4, 5, 4], layout: &[
+        (R0,0,10),(G0,0,10),(B0,0,10),(R1,0,4),(R0,10,1),(G3,4,1),(G2,0,4),(G1,0,5),(G0,10,1),(G3,0,4),(B1,0,4),
+        (B0,10,1),(B3,1,1),(B2,0,4),(R2,0,4),(B3,0,1),(B3,2,1),(R3,0,4),(G2,4,1),(B3,3,1)] },
+    Bc6hMode { mode: 0x0A, transformed: true, bits: [11, 4, 4, 5], layout: &[
+        (R0,0,10),(G0,0,10),(B0,0,10),(R1,0,4),(R0,10,1),(B2,4,1),(G2,0,4),(G1,0,4),(G0,10,1),(B3,0,1),(G3,0,4),
+        (B1,0,5),(B0,10,1),(B2,0,4),(R2,0,4),(B3,1,1),(B3,2,1),(R3,0,4),(B3,4,1),(B3,3,1)] },
+    Bc6hMode { mode: 0x0E, transformed: true, bits: [9, 5, 5, 5], layout: &[
+        (R0,0,9),(B2,4,1),(G0,0,9),(G2,4,1),(B0,0,9),(B3,4,1),(R1,0,5),(G3,4,1),(G2,0,4),(G1,0,5),(B3,0,1),
+        (G3,0,4),(B1,0,5),(B3,1,1),(B2,0,4),(R2,0,5),(B3,2,1),(R3,0,5),(B3,3,1)] },
+    Bc6hMode { mode: 0x12, transformed: true, bits: [8, 6, 5, 5], layout: &[
+        (R0,0,8),(G3,4,1),(B2,4,1),(G0,0,8),(B3,2,1),(G2,4,1),(B0,0,8),(B3,3,1),(B3,4,1),(R1,0,6),(G2,0,4),
+        (G1,0,5),(B3,0,1),(G3,0,4),(B1,0,5),(B3,1,1),(B2,0,4),(R2,0,6),(R3,0,6)] },
+    Bc6hMode { mode: 0x16, transformed: true, bits: [8, 5, 6, 5], layout: &[
+        (R0,0,8),(B3,0,1),(B2,4,1),(G0,0,8),(G2,5,1),(G2,4,1),(B0,0,8),(G3,5,1),(B3,4,1),(R1,0,5),(G3,4,1),
+        (G2,0,4),(G1,0,6),(G3,0,4),(B1,0,5),(B3,1,1),(B2,0,4),(R2,0,5),(B3,2,1),(R3,0,5),(B3,3,1)] },
+    Bc6hMode { mode: 0x1A, transformed: true, bits: [8, 5, 5, 6], layout: &[
+        (R0,0,8),(B3,1,1),(B2,4,1),(G0,0,8),(B2,5,1),(G2,4,1),(B0,0,8),(B3,5,1),(B3,4,1),(R1,0,5),(G3,4,1),
+        (G2,0,4),(G1,0,5),(B3,0,1),(G3,0,4),(B1,0,6),(B2,0,4),(R2,0,5),(B3,2,1),(R3,0,5),(B3,3,1)] },
+    Bc6hMode { mode: 0x1E, transformed: false, bits: [6, 6, 6, 6], layout: &[
+        (R0,0,6),(G3,4,1),(B3,0,1),(B3,1,1),(B2,4,1),(G0,0,6),(G2,5,1),(B2,5,1),(B3,2,1),(G2,4,1),(B0,0,6),
+        (G3,5,1),(B3,3,1),(B3,5,1),(B3,4,1),(R1,0,6),(G2,0,4),(G1,0,6),(G3,0,4),(B1,0,6),(B2,0,4),(R2,0,6),(R3,0,6)] },
+    Bc6hMode { mode: 0x03, transformed: false, bits: [10, 10, 10, 10], layout: &[
+        (R0,0,10),(G0,0,10),(B0,0,10),(R1,0,10),(G1,0,10),(B1,0,10)] },
+    Bc6hMode { mode: 0x07, transformed: true, bits: [11, 9, 9, 9], layout: &[
+        (R0,0,10),(G0,0,10),(B0,0,10),(R1,0,9),(R0,10,1),(G1,0,9),(G0,10,1),(B1,0,9),(B0,10,1)] },
+    Bc6hMode { mode: 0x0B, transformed: true, bits: [12, 8, 8, 8], layout: &[
+        (R0,0,10),(G0,0,10),(B0,0,10),(R1,0,8),(R0,11,1),(R0,10,1),(G1,0,8),(G0,11,1),(G0,10,1),
+        (B1,0,8),(B0,11,1),(B0,10,1)] },
+    Bc6hMode { mode: 0x0F, transformed: true, bits: [16, 4, 4, 4], layout: &[
+        (R0,0,10),(G0,0,10),(B0,0,10),(R1,0,4),(R0,15,1),(R0,14,1),(R0,13,1),(R0,12,1),(R0,11,1),(R0,10,1),
+        (G1,0,4),(G0,15,1),(G0,14,1),(G0,13,1),(G0,12,1),(G0,11,1),(G0,10,1),
+        (B1,0,4),(B0,15,1),(B0,14,1),(B0,13,1),(B0,12,1),(B0,11,1),(B0,10,1)] },
+];
+
+fn extend_sign(val: i32, bits: u32) -> i32 {
+    (val << (32 - bits)) >> (32 - bits)
+}
+
+fn bc6h_unquantize(val: i32, bits: u32, signed: bool) -> i32 {
+    if !signed {
+        if bits >= 15 || val == 0 {
+            val
+        } else if val == (1 << bits) - 1 {
+            0xFFFF
+        } else {
+            ((val << 16) + 0x8000) >> bits
+        }
+    } else if bits >= 16 || val == 0 {
+        val
+    } else {
+        let mag = val.abs();
+        let unq = if mag >= (1 << (bits - 1)) - 1 {
+            0x7FFF
+        } else {
+            ((mag << 15) + 0x4000) >> (bits - 1)
+        };
+        if val < 0 { -unq } else { unq }
+    }
+}
+
+// interpolated value to half float bits
+fn bc6h_finish_unquantize(val: i32, signed: bool) -> u16 {
+    if !signed {
+        ((val * 31) >> 6) as u16
+    } else if val < 0 {
+        0x8000 | (((-val) * 31) >> 5) as u16
+    } else {
+        ((val * 31) >> 5) as u16
+    }
+}
+
+pub fn half_to_f32(h: u16) -> f32 {
+    let sign = if h & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exp = ((h >> 10) & 0x1F) as i32;
+    let mantissa = (h & 0x3FF) as f32;
+    sign * match exp {
+        0 => mantissa * 2f32.powi(-24),
+        0x1F if mantissa == 0.0 => f32::INFINITY,
+        0x1F => f32::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f32.powi(exp - 15),
+    }
+}
+
+pub fn bc6h_decompress_block<F: FnMut(usize, usize, [f32; 4])>(
+    in_buf: &[u8; 16],
+    signed: bool,
+    mut writer: F,
+) {
+    let mut bit_stream = InputBitStream::new(u128::from_le_bytes(*in_buf));
+    let mut mode = bit_stream.read_bits32(2);
+    if mode > 1 {
+        mode |= bit_stream.read_bits32(3) << 2;
+    }
+    let Some(desc) = BC6H_MODES.iter().find(|desc| desc.mode == mode) else {
+        // reserved modes decode to black
+        for y in 0..4 {
+            for x in 0..4 {
+                writer(x, y, [0.0, 0.0, 0.0, 1.0])
+            }
+        }
+        return
+    };
+
+    let mut fields = [0i32; 12];
+    for &(field, first, count) in desc.layout {
+        fields[field as usize] |= (bit_stream.read_bits32(count as u32) as i32) << first;
+    }
+
+    // modes ending in 11 store a single region with 4 bit indices
+    let two_regions = mode & 3 != 3;
+    let endpoints = if two_regions { 4 } else { 2 };
+    let epb = desc.bits[0];
+    for c in 0..3 {
+        let channel = &mut fields[c * 4..c * 4 + 4];
+        if signed {
+            channel[0] = extend_sign(channel[0], epb);
+        }
+        if desc.transformed || signed {
+            for v in &mut channel[1..endpoints] {
+                *v = extend_sign(*v, desc.bits[c + 1]);
+            }
+        }
+        if desc.transformed {
+            for e in 1..endpoints {
+                channel[e] = (channel[e] + channel[0]) & ((1 << epb) - 1);
+                if signed {
+                    channel[e] = extend_sign(channel[e], epb);
+                }
+            }
+        }
+        for v in &mut channel[..endpoints] {
+            *v = bc6h_unquantize(*v, epb, signed);
+        }
+    }
+
+    let (part, index_bits, weights): (usize, u32, &[u32]) = if two_regions {
+        (bit_stream.read_bits32(5) as usize, 3, &WEIGHTS3)
+    } else {
+        (0, 4, &WEIGHTS4)
+    };
+    for i in 0..16 {
+        let anchor = i == 0 || (two_regions && i == ANCHOR_SECOND[part]);
+        let index = bit_stream.read_bits32(index_bits - anchor as u32) as usize;
+        let region = if two_regions { PARTITION2[part * 16 + i] } else { 0 };
+        let mut color = [1.0; 4];
+        for c in 0..3 {
+            let a = fields[c * 4 + region * 2];
+            let b = fields[c * 4 + region * 2 + 1];
+            let w = weights[index] as i32;
+            let v = (a * (64 - w) + b * w + 32) >> 6;
+            color[c] = half_to_f32(bc6h_finish_unquantize(v, signed));
+        }
+        writer(i % 4, i / 4, color)
+    }
+}
+
 const PACKET_LEN: usize = 16;
-#[allow(dead_code)]
 const BLOCK_LEN: usize = PACKET_LEN * 4 * 8;
 
 fn step<'a>(data: &'_ mut &'a [u8], max_len: usize) -> &'a [u8] {
@@ -474,6 +848,14 @@ fn step<'a>(data: &'_ mut &'a [u8], max_len: usize) -> &'a [u8] {
     ret
 }
 
+// How texels are ordered in the data. Block linear is the tiled layout used by console builds,
+// super blocks are 2^n GOBs (64 bytes by 8 rows) wide and high.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TexLayout {
+    Linear,
+    BlockLinear { super_width: usize, super_height: usize },
+}
+
 #[allow(dead_code)]
 pub trait TexCodec<const CELL_LEN: usize> {
     const CELL_WIDTH: usize;
@@ -486,13 +868,14 @@ pub trait TexCodec<const CELL_LEN: usize> {
         data: &[u8],
         width: usize,
         height: usize,
-        layout: u32,
+        layout: TexLayout,
         writer: F
     ) {
         match layout {
-            0xFFFFFFFF => Self::decode_image_linear(data, width, height, writer),
-            _ => ()
-            //_ => Self::decode_image_nsw(data, width, height, super_width, super_height, writer),
+            TexLayout::Linear => Self::decode_image_linear(data, width, height, writer),
+            TexLayout::BlockLinear { super_width, super_height } => {
+                Self::decode_image_nsw(data, width, height, super_width, super_height, writer)
+            },
         }
     }
 
@@ -528,6 +911,51 @@ pub trait TexCodec<const CELL_LEN: usize> {
         }
     }
 
+    // Linear layout, rows of cells are split over threads and decoded straight into out
+    fn decode_image_rows<P: Send, C: Fn(Self::T) -> P + Sync>(
+        data: &[u8],
+        width: usize,
+        height: usize,
+        threads: usize,
+        out: &mut [P],
+        convert: C,
+    ) {
+        let x_cells = width.div_ceil(Self::CELL_WIDTH);
+        let y_cells = height.div_ceil(Self::CELL_HEIGHT);
+        let row_len = x_cells * CELL_LEN;
+        let row_texels = width * Self::CELL_HEIGHT;
+        let decode_rows = |mut data: &[u8], out: &mut [P]| {
+            for out in out.chunks_mut(row_texels) {
+                let rows = out.len() / width;
+                for x_cell in 0..x_cells {
+                    let mut cell_buf = [0; CELL_LEN];
+                    let cell = step(&mut data, CELL_LEN);
+                    cell_buf[0..cell.len()].copy_from_slice(cell);
+                    Self::decode(&cell_buf, |x, y, v| {
+                        let x = x + x_cell * Self::CELL_WIDTH;
+                        if x < width && y < rows {
+                            out[y * width + x] = convert(v);
+                        }
+                    })
+                }
+            }
+        };
+
+        // small images aren't worth a thread
+        let rows_per_thread = usize::max(y_cells.div_ceil(threads.max(1)), 16);
+        let out = &mut out[..width * height];
+        if rows_per_thread >= y_cells {
+            return decode_rows(data, out);
+        }
+        let decode_rows = &decode_rows;
+        std::thread::scope(|scope| {
+            for (i, out) in out.chunks_mut(rows_per_thread * row_texels).enumerate() {
+                let data = data.get(i * rows_per_thread * row_len..).unwrap_or_default();
+                scope.spawn(move || decode_rows(data, out));
+            }
+        });
+    }
+
     fn decode_block<F: FnMut(usize, usize, Self::T)>(
         mut block: &[u8], /* BLOCK_LEN or less */
         mut writer: F,
@@ -614,9 +1042,21 @@ fn color6to8(value: u8) -> u8 {
 pub struct Bc1Unorm;
 
 impl Bc1Unorm {
-    fn decode_half<F: FnMut(usize, usize, [u8; 4])>(cell: &[u8; 8], mut writer: F) {
+    // the color half of bc2/bc3 always uses four colors
+    fn decode_half<F: FnMut(usize, usize, [u8; 4])>(cell: &[u8; 8], four_color: bool, mut writer: F) {
         let c0 = u16::from_le_bytes(cell[0..2].try_into().unwrap());
         let c1 = u16::from_le_bytes(cell[2..4].try_into().unwrap());
+        let colors = Self::palette(c0, c1, four_color);
+        for (y, &b) in cell[4..8].iter().enumerate() {
+            let (b0, b1, b2, b3) = b.bit_split((2, 2, 2, 2));
+            writer(0, y, colors[b0 as usize]);
+            writer(1, y, colors[b1 as usize]);
+            writer(2, y, colors[b2 as usize]);
+            writer(3, y, colors[b3 as usize]);
+        }
+    }
+
+    fn palette(c0: u16, c1: u16, four_color: bool) -> [[u8; 4]; 4] {
         let mut colors = [[0; 4]; 4];
         fn decode_color(c: u16) -> [u8; 4] {
             let (b, g, r) = c.bit_split((5, 6, 5));
@@ -629,7 +1069,7 @@ impl Bc1Unorm {
         }
         colors[0] = decode_color(c0);
         colors[1] = decode_color(c1);
-        if c0 > c1 {
+        if c0 > c1 || four_color {
             colors[2] = [
                 ((2 * colors[0][0] as u32 + colors[1][0] as u32) / 3) as u8,
                 ((2 * colors[0][1] as u32 + colors[1][1] as u32) / 3) as u8,
@@ -651,13 +1091,7 @@ impl Bc1Unorm {
             ];
             colors[3] = [0, 0, 0, 0];
         }
-        for (y, &b) in cell[4..8].iter().enumerate() {
-            let (b0, b1, b2, b3) = b.bit_split((2, 2, 2, 2));
-            writer(0, y, colors[b0 as usize]);
-            writer(1, y, colors[b1 as usize]);
-            writer(2, y, colors[b2 as usize]);
-            writer(3, y, colors[b3 as usize]);
-        }
+        colors
     }
 }
 
@@ -667,10 +1101,40 @@ impl TexCodec<8> for Bc1Unorm {
     type T = [u8; 4];
 
     fn decode<F: FnMut(usize, usize, Self::T)>(cell: &[u8; 8], mut writer: F) {
-        Self::decode_half(cell, &mut writer);
+        Self::decode_half(cell, false, &mut writer);
     }
 }
 
+pub struct Bc2Unorm;
+
+impl TexCodec<16> for Bc2Unorm {
+    const CELL_WIDTH: usize = 4;
+    const CELL_HEIGHT: usize = 4;
+    type T = [u8; 4];
+
+    fn decode<F: FnMut(usize, usize, Self::T)>(cell: &[u8; 16], mut writer: F) {
+        // 4 bit alpha per texel, then a bc1 color block
+        let alpha = u64::from_le_bytes(cell[0..8].try_into().unwrap());
+        Bc1Unorm::decode_half(cell[8..16].try_into().unwrap(), true, |x, y, v| {
+            let a = ((alpha >> ((y * 4 + x) * 4)) & 0xF) as u8;
+            writer(x, y, [v[0], v[1], v[2], a * 17])
+        });
+    }
+}
+
+pub struct Bc6h<const SIGNED: bool>;
+pub type Bc6hUf16 = Bc6h<false>;
+pub type Bc6hSf16 = Bc6h<true>;
+
+impl<const SIGNED: bool> TexCodec<16> for Bc6h<SIGNED> {
+    const CELL_WIDTH: usize = 4;
+    const CELL_HEIGHT: usize = 4;
+    type T = [f32; 4];
+
+    fn decode<F: FnMut(usize, usize, Self::T)>(cell: &[u8; 16], writer: F) {
+        bc6h_decompress_block(cell, SIGNED, writer);
+    }
+}
 
 pub struct Bc7Unorm;
 
@@ -715,7 +1179,7 @@ impl TexCodec<16> for Bc3Unorm {
         Bc4Unorm::decode_half(cell[0..8].try_into().unwrap(), |x, y, v| {
             alpha_buf[x][y] = v[0]
         });
-        Bc1Unorm::decode_half(cell[8..16].try_into().unwrap(), |x, y, v| {
+        Bc1Unorm::decode_half(cell[8..16].try_into().unwrap(), true, |x, y, v| {
             color_buf[x][y] = [v[0], v[1], v[2]]
         });
         for x in 0..4 {
@@ -731,9 +1195,23 @@ pub struct Bc4Unorm;
 
 impl Bc4Unorm {
     fn decode_half<F: FnMut(usize, usize, [u8; 4])>(cell: &[u8; 8], mut writer: F) {
+        let c = Self::palette(cell[0], cell[1]);
+        let mut buf = [0; 4];
+        for super_y in 0..2 {
+            buf[0..3].copy_from_slice(&cell[2 + super_y * 3..][..3]);
+            let mut a = u32::from_le_bytes(buf);
+            for y in 0..2 {
+                for x in 0..4 {
+                    let color = c[(a & 7) as usize];
+                    writer(x, y + super_y * 2, [color, color, color, 255]);
+                    a >>= 3;
+                }
+            }
+        }
+    }
+
+    fn palette(c0: u8, c1: u8) -> [u8; 8] {
         let mut c = [0; 8];
-        let c0 = cell[0];
-        let c1 = cell[1];
         c[0] = c0;
         c[1] = c1;
         if c[0] > c[1] {
@@ -751,18 +1229,7 @@ impl Bc4Unorm {
             c[6] = 0;
             c[7] = 255;
         }
-        let mut buf = [0; 4];
-        for super_y in 0..2 {
-            buf[0..3].copy_from_slice(&cell[2 + super_y * 3..][..3]);
-            let mut a = u32::from_le_bytes(buf);
-            for y in 0..2 {
-                for x in 0..4 {
-                    let color = c[(a & 7) as usize];
-                    writer(x, y + super_y * 2, [color, color, color, 255]);
-                    a >>= 3;
-                }
-            }
-        }
+        c
     }
 }
 
@@ -800,6 +1267,20 @@ impl TexCodec<16> for Bc5Unorm {
     }
 }
 
+// Passes cells through undecoded, to untile block compressed data without decoding it.
+// Dimensions given to it are in cells.
+pub struct RawCell<const N: usize>;
+
+impl<const N: usize> TexCodec<N> for RawCell<N> {
+    const CELL_WIDTH: usize = 1;
+    const CELL_HEIGHT: usize = 1;
+    type T = [u8; N];
+
+    fn decode<F: FnMut(usize, usize, Self::T)>(cell: &[u8; N], mut writer: F) {
+        writer(0, 0, *cell);
+    }
+}
+
 pub struct R8G8B8A8Unorm;
 
 impl TexCodec<4> for R8G8B8A8Unorm {
@@ -812,6 +1293,107 @@ impl TexCodec<4> for R8G8B8A8Unorm {
     }
 }
 
+pub struct B8G8R8A8Unorm;
+
+impl TexCodec<4> for B8G8R8A8Unorm {
+    const CELL_WIDTH: usize = 1;
+    const CELL_HEIGHT: usize = 1;
+    type T = [u8; 4];
+
+    fn decode<F: FnMut(usize, usize, Self::T)>(cell: &[u8; 4], mut writer: F) {
+        writer(0, 0, [cell[2], cell[1], cell[0], cell[3]]);
+    }
+}
+
+pub struct B8G8R8X8Unorm;
+
+impl TexCodec<4> for B8G8R8X8Unorm {
+    const CELL_WIDTH: usize = 1;
+    const CELL_HEIGHT: usize = 1;
+    type T = [u8; 4];
+
+    fn decode<F: FnMut(usize, usize, Self::T)>(cell: &[u8; 4], mut writer: F) {
+        writer(0, 0, [cell[2], cell[1], cell[0], 255]);
+    }
+}
+
+pub struct R10G10B10A2Unorm;
+
+impl TexCodec<4> for R10G10B10A2Unorm {
+    const CELL_WIDTH: usize = 1;
+    const CELL_HEIGHT: usize = 1;
+    type T = [f32; 4];
+
+    fn decode<F: FnMut(usize, usize, Self::T)>(cell: &[u8; 4], mut writer: F) {
+        let (r, g, b, a) = u32::from_le_bytes(*cell).bit_split((10, 10, 10, 2));
+        writer(0, 0, [r as f32 / 1023.0, g as f32 / 1023.0, b as f32 / 1023.0, a as f32 / 3.0]);
+    }
+}
+
+// unsigned small floats with a 5 bit exponent
+fn small_float(mantissa: u32, exponent: u32, mantissa_bits: u32) -> f32 {
+    let m = mantissa as f32 / (1 << mantissa_bits) as f32;
+    match exponent {
+        0 => m * 2f32.powi(-14),
+        31 if mantissa == 0 => f32::INFINITY,
+        31 => f32::NAN,
+        _ => (1.0 + m) * 2f32.powi(exponent as i32 - 15),
+    }
+}
+
+pub struct R11G11B10Float;
+
+impl TexCodec<4> for R11G11B10Float {
+    const CELL_WIDTH: usize = 1;
+    const CELL_HEIGHT: usize = 1;
+    type T = [f32; 4];
+
+    fn decode<F: FnMut(usize, usize, Self::T)>(cell: &[u8; 4], mut writer: F) {
+        let (rm, re, gm, ge, bm, be) = u32::from_le_bytes(*cell).bit_split((6, 5, 6, 5, 5, 5));
+        writer(0, 0, [small_float(rm, re, 6), small_float(gm, ge, 6), small_float(bm, be, 5), 1.0]);
+    }
+}
+
+// float formats, one channel ones are written as gray like R8Unorm
+macro_rules! float_codec {
+    ($name:ident, $channels:literal, $size:literal, $read:expr) => {
+        pub struct $name;
+
+        impl TexCodec<{ $channels * $size }> for $name {
+            const CELL_WIDTH: usize = 1;
+            const CELL_HEIGHT: usize = 1;
+            type T = [f32; 4];
+
+            fn decode<F: FnMut(usize, usize, Self::T)>(cell: &[u8; $channels * $size], mut writer: F) {
+                let mut v = [0.0, 0.0, 0.0, 1.0];
+                for (c, bytes) in cell.chunks_exact($size).enumerate() {
+                    v[c] = $read(bytes);
+                }
+                if $channels == 1 {
+                    v = [v[0], v[0], v[0], 1.0];
+                }
+                writer(0, 0, v);
+            }
+        }
+    };
+}
+
+fn read_half(bytes: &[u8]) -> f32 {
+    half_to_f32(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_f32(bytes: &[u8]) -> f32 {
+    f32::from_le_bytes(bytes.try_into().unwrap())
+}
+
+float_codec!(R32G32B32A32Float, 4, 4, read_f32);
+float_codec!(R32G32B32Float, 3, 4, read_f32);
+float_codec!(R32G32Float, 2, 4, read_f32);
+float_codec!(R32Float, 1, 4, read_f32);
+float_codec!(R16G16B16A16Float, 4, 2, read_half);
+float_codec!(R16G16Float, 2, 2, read_half);
+float_codec!(R16Float, 1, 2, read_half);
+
 pub struct R8Unorm;
 
 impl TexCodec<1> for R8Unorm {
@@ -838,3 +1420,391 @@ impl TexCodec<2> for R8G8Unorm {
         writer(0, 0, [r, g, 0, 255])
     }
 }
+
+// Encoders, the other way around from TexCodec. Texels come in rows of CELL_WIDTH,
+// cells hanging over the edge of the image repeat the last row/column.
+pub trait TexEncoder<const CELL_LEN: usize>: TexCodec<CELL_LEN> {
+    fn encode(texels: &[[u8; 4]]) -> [u8; CELL_LEN];
+
+    fn encode_image(rgba: &[u8], width: usize, height: usize) -> Vec<u8> {
+        let x_cells = width.div_ceil(Self::CELL_WIDTH);
+        let y_cells = height.div_ceil(Self::CELL_HEIGHT);
+        let mut out = Vec::with_capacity(x_cells * y_cells * CELL_LEN);
+        let mut texels = vec![[0; 4]; Self::CELL_WIDTH * Self::CELL_HEIGHT];
+        for y_cell in 0..y_cells {
+            for x_cell in 0..x_cells {
+                for y in 0..Self::CELL_HEIGHT {
+                    for x in 0..Self::CELL_WIDTH {
+                        let px = usize::min(x_cell * Self::CELL_WIDTH + x, width - 1);
+                        let py = usize::min(y_cell * Self::CELL_HEIGHT + y, height - 1);
+                        texels[y * Self::CELL_WIDTH + x] = rgba[(py * width + px) * 4..][..4].try_into().unwrap();
+                    }
+                }
+                out.extend(Self::encode(&texels));
+            }
+        }
+        out
+    }
+}
+
+impl CompressionType {
+    // None if there's no encoder for the format
+    pub fn encode_image(&self, rgba: &[u8], width: usize, height: usize) -> Option<Vec<u8>> {
+        Some(match self {
+            Self::R8G8B8A8Unorm => R8G8B8A8Unorm::encode_image(rgba, width, height),
+            Self::Bc1Unorm => Bc1Unorm::encode_image(rgba, width, height),
+            Self::Bc3Unorm => Bc3Unorm::encode_image(rgba, width, height),
+            Self::Bc4Unorm => Bc4Unorm::encode_image(rgba, width, height),
+            Self::Bc5Unorm => Bc5Unorm::encode_image(rgba, width, height),
+            Self::Bc7Unorm => Bc7Unorm::encode_image(rgba, width, height),
+            _ => return None,
+        })
+    }
+}
+
+fn color_distance(a: &[u8], b: &[u8]) -> u32 {
+    a.iter().zip(b).map(|(&a, &b)| (a as i32 - b as i32).pow(2) as u32).sum()
+}
+
+fn nearest(palette: &[[u8; 4]], texel: &[u8], channels: usize) -> usize {
+    (0..palette.len())
+        .min_by_key(|&i| color_distance(&palette[i][..channels], &texel[..channels]))
+        .unwrap()
+}
+
+// The two texels furthest apart along the main axis of the colors, a cheap stand in for
+// a proper endpoint search
+fn principal_endpoints(texels: &[[u8; 4]], channels: usize) -> ([f32; 4], [f32; 4]) {
+    let n = texels.len() as f32;
+    let mut mean = [0.0f32; 4];
+    for t in texels {
+        for c in 0..channels {
+            mean[c] += t[c] as f32 / n;
+        }
+    }
+    let mut cov = [[0.0f32; 4]; 4];
+    for t in texels {
+        for i in 0..channels {
+            for j in 0..channels {
+                cov[i][j] += (t[i] as f32 - mean[i]) * (t[j] as f32 - mean[j]);
+            }
+        }
+    }
+    let mut axis = [1.0f32; 4];
+    for _ in 0..8 {
+        let mut next = [0.0f32; 4];
+        for i in 0..channels {
+            for j in 0..channels {
+                next[i] += cov[i][j] * axis[j];
+            }
+        }
+        let len = next.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if len < 1e-6 {
+            break
+        }
+        axis = next.map(|v| v / len);
+    }
+    let project = |t: &[u8; 4]| (0..channels).map(|c| (t[c] as f32 - mean[c]) * axis[c]).sum::<f32>();
+    let min = texels.iter().min_by(|a, b| project(a).total_cmp(&project(b))).unwrap();
+    let max = texels.iter().max_by(|a, b| project(a).total_cmp(&project(b))).unwrap();
+    (min.map(|v| v as f32), max.map(|v| v as f32))
+}
+
+impl Bc1Unorm {
+    fn encode_half(texels: &[[u8; 4]], four_color: bool) -> [u8; 8] {
+        let to565 = |c: [f32; 4]| {
+            let r = (c[0] * 31.0 / 255.0).round() as u16;
+            let g = (c[1] * 63.0 / 255.0).round() as u16;
+            let b = (c[2] * 31.0 / 255.0).round() as u16;
+            (r << 11) | (g << 5) | b
+        };
+        let transparent = !four_color && texels.iter().any(|t| t[3] < 128);
+        let opaque: Vec<[u8; 4]> = texels.iter().copied().filter(|t| four_color || t[3] >= 128).collect();
+        let (lo, hi) = if opaque.is_empty() { ([0.0; 4], [0.0; 4]) } else { principal_endpoints(&opaque, 3) };
+        let (mut c0, mut c1) = (to565(hi), to565(lo));
+        // c0 > c1 picks four colors, c0 <= c1 three colors and transparent black
+        if (c0 < c1) != transparent {
+            std::mem::swap(&mut c0, &mut c1);
+        }
+        let palette = Self::palette(c0, c1, four_color);
+        let colors = if c0 > c1 || four_color { &palette[..] } else { &palette[..3] };
+        let mut out = [0; 8];
+        out[0..2].copy_from_slice(&c0.to_le_bytes());
+        out[2..4].copy_from_slice(&c1.to_le_bytes());
+        for (i, t) in texels.iter().enumerate() {
+            let index = if transparent && t[3] < 128 { 3 } else { nearest(colors, t, 3) };
+            out[4 + i / 4] |= (index as u8) << ((i % 4) * 2);
+        }
+        out
+    }
+}
+
+impl TexEncoder<8> for Bc1Unorm {
+    fn encode(texels: &[[u8; 4]]) -> [u8; 8] {
+        Self::encode_half(texels, false)
+    }
+}
+
+impl Bc4Unorm {
+    fn encode_half(values: &[u8]) -> [u8; 8] {
+        let max = *values.iter().max().unwrap();
+        let min = *values.iter().min().unwrap();
+        // max > min for the eight value mode, all indices are 0 if they're equal anyway
+        let palette = Self::palette(max, min).map(|v| [v, 0, 0, 0]);
+        let mut indices: u64 = 0;
+        for (i, &v) in values.iter().enumerate() {
+            indices |= (nearest(&palette, &[v], 1) as u64) << (i * 3);
+        }
+        let mut out = [0; 8];
+        out[0] = max;
+        out[1] = min;
+        out[2..8].copy_from_slice(&indices.to_le_bytes()[..6]);
+        out
+    }
+}
+
+impl TexEncoder<8> for Bc4Unorm {
+    fn encode(texels: &[[u8; 4]]) -> [u8; 8] {
+        Self::encode_half(&texels.iter().map(|t| t[0]).collect::<Vec<_>>())
+    }
+}
+
+impl TexEncoder<16> for Bc3Unorm {
+    fn encode(texels: &[[u8; 4]]) -> [u8; 16] {
+        let mut out = [0; 16];
+        out[0..8].copy_from_slice(&Bc4Unorm::encode_half(&texels.iter().map(|t| t[3]).collect::<Vec<_>>()));
+        out[8..16].copy_from_slice(&Bc1Unorm::encode_half(texels, true));
+        out
+    }
+}
+
+impl TexEncoder<16> for Bc5Unorm {
+    fn encode(texels: &[[u8; 4]]) -> [u8; 16] {
+        let mut out = [0; 16];
+        out[0..8].copy_from_slice(&Bc4Unorm::encode_half(&texels.iter().map(|t| t[0]).collect::<Vec<_>>()));
+        out[8..16].copy_from_slice(&Bc4Unorm::encode_half(&texels.iter().map(|t| t[1]).collect::<Vec<_>>()));
+        out
+    }
+}
+
+// Only mode 6 (one subset, rgba 7.7.7.7 + p bit, 4 bit indices), it handles any block reasonably
+impl TexEncoder<16> for Bc7Unorm {
+    fn encode(texels: &[[u8; 4]]) -> [u8; 16] {
+        let (lo, hi) = principal_endpoints(texels, 4);
+        // the p bit shared by the channels of an endpoint that loses the least
+        let quantize = |c: [f32; 4]| {
+            (0..2u32).map(|p| {
+                let v = c.map(|v| ((v - p as f32) / 2.0).round().clamp(0.0, 127.0) as u32);
+                let err: f32 = (0..4).map(|i| (((v[i] << 1) | p) as f32 - c[i]).powi(2)).sum();
+                (v, p, err)
+            }).min_by(|a, b| a.2.total_cmp(&b.2)).map(|(v, p, _)| (v, p)).unwrap()
+        };
+        let (mut e0, mut p0) = quantize(lo);
+        let (mut e1, mut p1) = quantize(hi);
+
+        let palette = |e0: [u32; 4], p0: u32, e1: [u32; 4], p1: u32| -> [[u8; 4]; 16] {
+            std::array::from_fn(|i| {
+                let w = WEIGHTS4[i];
+                std::array::from_fn(|c| {
+                    let (a, b) = ((e0[c] << 1) | p0, (e1[c] << 1) | p1);
+                    ((a * (64 - w) + b * w + 32) >> 6) as u8
+                })
+            })
+        };
+        let mut colors = palette(e0, p0, e1, p1);
+        let mut indices: Vec<usize> = texels.iter().map(|t| nearest(&colors, t, 4)).collect();
+        // the first index has no top bit, flip the endpoints if it would need one
+        if indices[0] >= 8 {
+            std::mem::swap(&mut e0, &mut e1);
+            std::mem::swap(&mut p0, &mut p1);
+            colors = palette(e0, p0, e1, p1);
+            indices = texels.iter().map(|t| nearest(&colors, t, 4)).collect();
+        }
+
+        let mut block: u128 = 1 << 6;
+        let mut pos = 7;
+        let mut push = |value: u32, bits: u32| {
+            block |= (value as u128) << pos;
+            pos += bits;
+        };
+        for c in 0..4 {
+            push(e0[c], 7);
+            push(e1[c], 7);
+        }
+        push(p0, 1);
+        push(p1, 1);
+        for (i, &index) in indices.iter().enumerate() {
+            push(index as u32, if i == 0 { 3 } else { 4 });
+        }
+        block.to_le_bytes()
+    }
+}
+
+impl TexEncoder<4> for R8G8B8A8Unorm {
+    fn encode(texels: &[[u8; 4]]) -> [u8; 4] {
+        texels[0]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_both<P: Texel + Send + PartialEq + std::fmt::Debug>(name: &str, width: usize, height: usize, threads: usize) {
+        let format = TexFormat::from_name(name).unwrap();
+        let data: Vec<u8> = (0..format.row_len(width) * height.div_ceil(format.kind.cell().0))
+            .map(|i| ((i as u32).wrapping_mul(2654435761) >> 13) as u8)
+            .collect();
+        let mut single = vec![P::from_unorm8([0; 4]); width * height];
+        format.kind.decode_image(&data, width, height, TexLayout::Linear, |x, y, v: P| single[y * width + x] = v);
+        let threaded = format.kind.decode_linear::<P>(&data, width, height, threads);
+        assert!(threaded == single, "{name} {width}x{height} on {threads} threads");
+    }
+
+    // 53 rows of cells over 3 threads splits into 18, 18 and 17 rows, past the 16 row minimum,
+    // and the width leaves a partial column of cells
+    #[test]
+    fn threaded_rows_match_single_thread() {
+        for name in ["BC1_UNORM", "BC5_UNORM", "BC7_UNORM"] {
+            decode_both::<[u8; 4]>(name, 37, 53 * 4 - 1, 3);
+        }
+        decode_both::<[f32; 4]>("BC6H_UF16", 37, 53 * 4 - 1, 3);
+        decode_both::<[u8; 4]>("R8G8B8A8_UNORM", 37, 53, 3);
+    }
+
+    // Byte offset of (x in bytes, y) in Tegra X1 block linear memory with blocks one GOB wide
+    // and 2^block_height GOBs high, the same GOB address formula as the yuzu and Ryujinx
+    // texture swizzlers: 64 byte by 8 row GOBs of 16 byte by 2 row sectors, blocks left to right.
+    fn block_linear_offset(x: usize, y: usize, width_bytes: usize, block_height: u32) -> usize {
+        let gob_rows = 1 << block_height;
+        let block_len = 512 * gob_rows;
+        let blocks_x = width_bytes.div_ceil(64);
+        (y / (8 * gob_rows)) * blocks_x * block_len
+            + (x / 64) * block_len
+            + ((y % (8 * gob_rows)) / 8) * 512
+            + ((x % 64) / 32) * 256
+            + ((y % 8) / 2) * 64
+            + ((x % 32) / 16) * 32
+            + (y % 2) * 16
+            + x % 16
+    }
+
+    // tiles a known image with the reference formula, untiling has to give the image back,
+    // the size leaves partial GOBs and blocks on both axes
+    #[test]
+    fn block_linear_untiles() {
+        let (width, height): (usize, usize) = (40, 21);
+        for block_height in [0, 1, 2] {
+            let texel = |x: usize, y: usize| [x as u8, y as u8, (x * 7 + y * 3) as u8, 0xA5];
+            let width_bytes = width * 4;
+            let rows = height.div_ceil(8 << block_height) * (8 << block_height);
+            let mut tiled = vec![0u8; width_bytes.div_ceil(64) * 64 * rows];
+            for y in 0..height {
+                for x in 0..width {
+                    let offset = block_linear_offset(x * 4, y, width_bytes, block_height);
+                    tiled[offset..offset + 4].copy_from_slice(&texel(x, y));
+                }
+            }
+
+            let format = TexFormat::from_name("R8G8B8A8_UNORM").unwrap();
+            let layout = TexLayout::BlockLinear { super_width: 1, super_height: 1 << block_height };
+            let mut out = vec![[0u8; 4]; width * height];
+            format.kind.decode_image(&tiled, width, height, layout, |x, y, v: [u8; 4]| out[y * width + x] = v);
+            for y in 0..height {
+                for x in 0..width {
+                    assert_eq!(out[y * width + x], texel(x, y), "({x}, {y}) with block height {block_height}");
+                }
+            }
+        }
+    }
+
+    // Known blocks, the expected half floats were worked out from the BC6H format spec
+    // (bit layouts, unquantize and finish_unquantize) independently of this decoder
+    #[rustfmt::skip]
+    const BC6H_BLOCKS: [(bool, [u8; 16], [[u16; 3]; 16]); 4] = [
+        // BC6H_UF16 mode 0x03, one region: raw 10 bit endpoints (0, 100, 1023) and (1023, 500, 0),
+        // texel i uses index i
+        (false, [0x03, 0x00, 0x32, 0xFE, 0xFF, 0x9F, 0x3E, 0x00, 0x10, 0x32, 0x54, 0x76, 0x98, 0xBA, 0xDC, 0xFE], [
+            [0x0000, 0x0C2B, 0x7BFF], [0x07C0, 0x0F32, 0x743F], [0x1170, 0x12FB, 0x6A8F], [0x1930, 0x1602, 0x62CF],
+            [0x20F0, 0x1909, 0x5B0F], [0x28B0, 0x1C10, 0x534F], [0x3260, 0x1FD9, 0x499F], [0x3A20, 0x22E0, 0x41DF],
+            [0x41DF, 0x25E7, 0x3A20], [0x499F, 0x28EE, 0x3260], [0x534F, 0x2CB6, 0x28B0], [0x5B0F, 0x2FBD, 0x20F0],
+            [0x62CF, 0x32C4, 0x1930], [0x6A8F, 0x35CB, 0x1170], [0x743F, 0x3994, 0x07C0], [0x7BFF, 0x3C9B, 0x0000],
+        ]),
+        // BC6H_SF16 mode 0x03, one region: endpoints (-200, 0, 511) and (300, -512, -1), 511 and -512
+        // saturate to the half float max
+        (true, [0x03, 0x67, 0x00, 0xFE, 0x63, 0x09, 0xC0, 0xFF, 0x71, 0x5E, 0x3C, 0x1A, 0xF8, 0xD6, 0xB4, 0x92], [
+            [0xB08F, 0x0000, 0x7BFF], [0x0851, 0xBA20, 0x41B3], [0x4131, 0xF43F, 0x0768], [0x88BE, 0xA8B0, 0x5331],
+            [0x3021, 0xE2CF, 0x18E5], [0x97E9, 0x9930, 0x62BC], [0x20F6, 0xD34F, 0x2871], [0xA8F9, 0x87C0, 0x7439],
+            [0x0FE6, 0xC1DF, 0x39EE], [0x48C7, 0xFBFF, 0x805D], [0x00BB, 0xB260, 0x4979], [0x37B6, 0xEA8F, 0x111F],
+            [0x9054, 0xA0F0, 0x5AF6], [0x288C, 0xDB0F, 0x20AB], [0x9F7E, 0x9170, 0x6A82], [0x177C, 0xC99F, 0x3228],
+        ]),
+        // BC6H_SF16 mode 0x1E, two regions with partition 0: raw 6 bit endpoints (5, -3, 31) (-32, 12, 0)
+        // and (20, -20, 1) (-1, 30, -17)
+        (true, [0xBE, 0xB8, 0xBE, 0x3E, 0x03, 0x99, 0x71, 0x20, 0xA8, 0x1F, 0xC4, 0xAB, 0x98, 0xC3, 0xAB, 0x98], [
+            [0x00E0, 0x84D8, 0x6A8F], [0xBEB1, 0x1648, 0x3450], [0x85D0, 0x7630, 0xC3D0], [0x3776, 0x97DB, 0x8EE5],
+            [0xD320, 0x1F00, 0x22E0], [0x1550, 0x8D90, 0x7BFF], [0x2B79, 0x03EF, 0x993F], [0x062D, 0x5A65, 0xB975],
+            [0x00E0, 0x84D8, 0x6A8F], [0xBEB1, 0x1648, 0x3450], [0x85D0, 0x7630, 0xC3D0], [0x3776, 0x97DB, 0x8EE5],
+            [0xD320, 0x1F00, 0x22E0], [0x1550, 0x8D90, 0x7BFF], [0x2B79, 0x03EF, 0x993F], [0x3776, 0x97DB, 0x8EE5],
+        ]),
+        // BC6H_UF16 mode 0x00, two regions with partition 0: base (600, 40, 1000) and 5 bit deltas
+        // (-16, 15, 3) (7, -9, -1) (0, 1, -16)
+        (false, [0x1C, 0x4B, 0x14, 0xD0, 0x87, 0xEE, 0x89, 0xE1, 0x0F, 0x00, 0xC4, 0x29, 0xB8, 0xCB, 0x29, 0xB8], [
+            [0x4871, 0x0528, 0x7934], [0x4798, 0x05F4, 0x795D], [0x4934, 0x0453, 0x7844], [0x4953, 0x0427, 0x7885],
+            [0x4871, 0x0528, 0x7934], [0x48B7, 0x04E7, 0x7927], [0x48B7, 0x0506, 0x7737], [0x48D6, 0x04DA, 0x7778],
+            [0x4753, 0x0635, 0x796A], [0x4798, 0x05F4, 0x795D], [0x4934, 0x0453, 0x7844], [0x4953, 0x0427, 0x7885],
+            [0x4871, 0x0528, 0x7934], [0x48B7, 0x04E7, 0x7927], [0x48B7, 0x0506, 0x7737], [0x4953, 0x0427, 0x7885],
+        ]),
+    ];
+
+    #[test]
+    fn bc6h_known_blocks() {
+        assert_eq!(half_to_f32(0x7BFF), 65504.0);
+        assert_eq!(half_to_f32(0xC000), -2.0);
+        assert_eq!(half_to_f32(0x0001), 2f32.powi(-24));
+        for (i, (signed, block, expected)) in BC6H_BLOCKS.iter().enumerate() {
+            let mut texels = [[0.0; 4]; 16];
+            bc6h_decompress_block(block, *signed, |x, y, v| texels[y * 4 + x] = v);
+            for (t, (texel, expected)) in texels.iter().zip(expected).enumerate() {
+                let expected = [half_to_f32(expected[0]), half_to_f32(expected[1]), half_to_f32(expected[2]), 1.0];
+                assert_eq!(*texel, expected, "block {i} texel {t}");
+            }
+        }
+    }
+
+    // (format, channels the format keeps, largest and mean allowed difference per channel)
+    const ENCODE_TOLERANCES: [(&str, usize, u8, f32); 6] = [
+        ("R8G8B8A8_UNORM", 4, 0, 0.0),
+        ("BC1_UNORM", 3, 32, 8.0),
+        ("BC3_UNORM", 4, 32, 6.0),
+        ("BC4_UNORM", 1, 4, 1.0),
+        ("BC5_UNORM", 2, 4, 1.0),
+        ("BC7_UNORM", 4, 32, 6.0),
+    ];
+
+    // a smooth image with every channel different, the size leaves partial cells on both axes
+    #[test]
+    fn encode_decode_round_trip() {
+        let (width, height): (usize, usize) = (19, 13);
+        let rgba: Vec<u8> = (0..width * height)
+            .flat_map(|i| {
+                let (x, y) = (i % width, i / width);
+                [x * 12, y * 18, (x + y) * 7, 255 - x * 3 - y * 2].map(|v| v as u8)
+            })
+            .collect();
+        for (name, channels, max_diff, mean_diff) in ENCODE_TOLERANCES {
+            let format = TexFormat::from_name(name).unwrap();
+            let data = format.kind.encode_image(&rgba, width, height).unwrap();
+            assert_eq!(data.len(), format.row_len(width) * height.div_ceil(format.kind.cell().0), "{name} size");
+            let decoded = format.kind.decode_linear::<[u8; 4]>(&data, width, height, 1);
+            let (mut worst, mut total) = (0, 0);
+            for (texel, source) in decoded.iter().zip(rgba.chunks_exact(4)) {
+                for c in 0..channels {
+                    worst = worst.max(texel[c].abs_diff(source[c]));
+                    total += texel[c].abs_diff(source[c]) as usize;
+                }
+            }
+            let mean = total as f32 / (width * height * channels) as f32;
+            assert!(worst <= max_diff && mean <= mean_diff, "{name} is off by up to {worst}, {mean} on average");
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use std::io::Write;
+
+use crate::reerr::Result;
+use crate::tex::Tex;
+
+const DDSD_CAPS: u32 = 0x1;
+const DDSD_HEIGHT: u32 = 0x2;
+const DDSD_WIDTH: u32 = 0x4;
+const DDSD_PITCH: u32 = 0x8;
+const DDSD_PIXELFORMAT: u32 = 0x1000;
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+const DDSD_LINEARSIZE: u32 = 0x80000;
+const DDSD_DEPTH: u32 = 0x800000;
+
+const DDPF_FOURCC: u32 = 0x4;
+
+const DDSCAPS_COMPLEX: u32 = 0x8;
+const DDSCAPS_TEXTURE: u32 = 0x1000;
+const DDSCAPS_MIPMAP: u32 = 0x400000;
+
+const DDSCAPS2_CUBEMAP_ALLFACES: u32 = 0xFE00;
+const DDSCAPS2_VOLUME: u32 = 0x200000;
+
+const D3D10_RESOURCE_DIMENSION_TEXTURE2D: u32 = 3;
+const D3D10_RESOURCE_DIMENSION_TEXTURE3D: u32 = 4;
+const D3D10_RESOURCE_MISC_TEXTURECUBE: u32 = 0x4;
+
+// Writes the tex data as is with a DX10 header, every array slice/face with all of its mips
+pub fn write_dds<W: Write>(tex: &Tex, mut writer: W) -> Result<()> {
+    let (width, height, depth) = tex.mip_size(0);
+    let mip_count = tex.mip_count().max(1);
+    let cubemap = tex.is_cubemap();
+    let volume = depth > 1;
+    // tex format codes are DXGI_FORMAT values
+    let format = tex.tex_format()?;
+    let compressed = format.kind.is_block_compressed();
+    let pitch = format.row_len(width as usize) as u32;
+    let rows = (height as usize).div_ceil(format.kind.cell().0) as u32;
+
+    let mut flags = DDSD_CAPS | DDSD_HEIGHT | DDSD_WIDTH | DDSD_PIXELFORMAT | DDSD_MIPMAPCOUNT;
+    flags |= if compressed { DDSD_LINEARSIZE } else { DDSD_PITCH };
+    if volume {
+        flags |= DDSD_DEPTH;
+    }
+    let mut caps = DDSCAPS_TEXTURE;
+    if mip_count > 1 {
+        caps |= DDSCAPS_COMPLEX | DDSCAPS_MIPMAP;
+    }
+    if cubemap || volume {
+        caps |= DDSCAPS_COMPLEX;
+    }
+    let caps2 = if cubemap {
+        DDSCAPS2_CUBEMAP_ALLFACES
+    } else if volume {
+        DDSCAPS2_VOLUME
+    } else {
+        0
+    };
+
+    writer.write_all(b"DDS ")?;
+    writer.write_all(&124u32.to_le_bytes())?;
+    writer.write_all(&flags.to_le_bytes())?;
+    writer.write_all(&height.to_le_bytes())?;
+    writer.write_all(&width.to_le_bytes())?;
+    writer.write_all(&u32::to_le_bytes(if compressed { pitch * rows } else { pitch }))?;
+    writer.write_all(&u32::to_le_bytes(if volume { depth } else { 0 }))?;
+    writer.write_all(&(mip_count as u32).to_le_bytes())?;
+    writer.write_all(&[0; 11 * 4])?;
+
+    // pixel format, the real one is in the DX10 header
+    writer.write_all(&32u32.to_le_bytes())?;
+    writer.write_all(&DDPF_FOURCC.to_le_bytes())?;
+    writer.write_all(b"DX10")?;
+    writer.write_all(&[0; 5 * 4])?;
+
+    writer.write_all(&caps.to_le_bytes())?;
+    writer.write_all(&caps2.to_le_bytes())?;
+    writer.write_all(&[0; 3 * 4])?;
+
+    let tex_count = tex.tex_count().max(1);
+    writer.write_all(&format.code.to_le_bytes())?;
+    writer.write_all(&u32::to_le_bytes(if volume { D3D10_RESOURCE_DIMENSION_TEXTURE3D } else { D3D10_RESOURCE_DIMENSION_TEXTURE2D }))?;
+    writer.write_all(&u32::to_le_bytes(if cubemap { D3D10_RESOURCE_MISC_TEXTURECUBE } else { 0 }))?;
+    writer.write_all(&(if cubemap { tex_count / 6 } else { tex_count } as u32).to_le_bytes())?;
+    writer.write_all(&0u32.to_le_bytes())?;
+
+    for tex_idx in 0..tex_count {
+        for mip_idx in 0..mip_count {
+            for slice_idx in 0..tex.slice_count(tex_idx, mip_idx) {
+                writer.write_all(&tex.linear_slice(tex_idx, mip_idx, slice_idx)?)?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -1,6 +1,6 @@
 use core::str;
 use std::{
-    collections::{HashMap, HashSet}, io::{Read, Seek}, sync::OnceLock
+    collections::{HashMap, HashSet}, io::{Cursor, Read, Seek, Write}, sync::OnceLock
 };
 
 use crate::file_ext::*;
@@ -11,8 +11,8 @@ pub static ENUM_FILE: OnceLock<String> = OnceLock::new();
 use nalgebra_glm::{Mat4x4, Vec2, Vec3, Vec4};
 use serde::{ser::{SerializeSeq, SerializeStruct}, Deserialize, Serialize};
 use uuid::Uuid;
-use crate::rsz::TypeDescriptor;
-use crate::reerr::{Result, FileParseError::*};
+use crate::rsz::{Rsz, TypeDescriptor};
+use crate::reerr::{Context, Result, FileParseError::*};
 
 
 #[derive(Debug, Clone)]
@@ -77,14 +77,16 @@ pub enum RszType {
     RuntimeType(String),
     Struct(RszStruct<RszType>),
     Enum(Box<RszType>, String),
-    OBB,
+    OBB((Mat4x4, Vec3)),
     Data(Vec<u8>),
 
-    Nullable(Box<Option<RszType>>, String, String, String, String),
+    // the value, or Null when the has-value flag is clear, and the value type name
+    Nullable(Box<RszType>, String),
 }
 
 impl RszType {
-    fn from_field<F: Read + Seek>(data: &mut F, field: &RszField) -> Result<RszType> {
+    // base is where data starts in the file, nested structs use it to report absolute offsets
+    fn from_field<F: Read + Seek>(data: &mut F, field: &RszField, base: Option<u64>) -> Result<RszType> {
         data.seek_align_up(field.align.into())?;
         let r#type = match field.r#type.as_str() {
             "S8" => RszType::Int8(data.read_i8()?),
@@ -158,8 +160,11 @@ impl RszType {
                 RszType::Rect((data.read_u32()?, data.read_u32()?, data.read_u32()?, data.read_u32()?))
             },
             "OBB" => {
-                data.seek_relative(field.size.into())?;
-                RszType::OBB
+                // coord matrix followed by the half extents, padded to 16
+                let coord = data.read_f32m4x4()?;
+                let extent = data.read_f32vec3()?;
+                data.seek_align_up(16)?;
+                RszType::OBB((coord, extent))
             },
             "Guid" => {
                 let mut buf = [0; 16];
@@ -188,43 +193,30 @@ impl RszType {
                 let r#og_type = &field.original_type.replace("[]", "");
                 if let Some(mapped_hash) = RszDump::name_map().get(r#og_type) {
                     if let Some(r#struct) = RszDump::rsz_map().get(&mapped_hash) {
-                        let v = RszDump::parse_struct(data, TypeDescriptor{hash: *mapped_hash, crc: r#struct.crc})?;
-                        x = RszType::Struct(v)
+                        // the error path continues from this field, so only the field names are added
+                        let fields = RszDump::parse_fields(data, r#struct, base)?;
+                        x = RszType::Struct(RszValue { name: r#struct.name.clone(), crc: r#struct.crc, fields })
                     } else {
                         return Err(format!("Name hash not in hash map {:X}", mapped_hash).into())
                     };
-                } else if r#og_type.contains("System.Nullable") {
-                    //"System.Nullable`1[[via.vec3, System.Private.CoreLib, Version=1.0.0.0, Culture=neutral, PublicKeyToken=null]][];
-                    // case 1 `1[[
-                    let stripped = r#og_type.replace("System.Nullable`1[[", "").replace("]]", "");
-                    let splitted: Vec<&str> = stripped.split(", ").collect();
-                    let r#type = *splitted.get(0).unwrap();
-                    let lib = *splitted.get(1).unwrap();
-                    let version = *splitted.get(2).unwrap();
-                    let culture = *splitted.get(3).unwrap();
-                    let public_key_token = *splitted.get(4).unwrap();
-                    println!("{} {} {} {} {}", r#type, lib, version, culture, public_key_token);
-                    let _hash = RszDump::name_map().get(&r#type.to_string()).unwrap();
-                    let is_null = data.read_u32()? != 0; // idk if this is actually in the right
-                                                        // spot at all. could be a u32 or u8?
-                    let rsz_value = match r#type {
-                        "via.vec3" => {
-                            let x = RszType::Vec3(data.read_f32vec3()?);
-                            data.seek(std::io::SeekFrom::Current(4))?;
-                            x
-                        },
-                        "via.Quaternion" => {
-                            let x = RszType::Quaternion((data.read_f32()?, data.read_f32()?, data.read_f32()?, data.read_f32()?));
-                            x
-                        }
-                        _ => RszType::Null
-                    };
-                    data.seek_align_up(16)?;
-                    x = if !is_null {
-                        RszType::Nullable(Box::new(Some(rsz_value)), lib.to_string(), version.to_string(), culture.to_string(), public_key_token.to_string())
-                    } else {
-                        RszType::Nullable(Box::new(Some(RszType::Null)), lib.to_string(), version.to_string(), culture.to_string(), public_key_token.to_string())
+                } else if let Some(inner) = nullable_inner_type(r#og_type) {
+                    // laid out like the .NET struct, a bool has-value flag followed by the
+                    // value at its own alignment, so the padding in between must be zero
+                    let start = data.stream_position()?;
+                    let has_value = data.read_u8()?;
+                    if has_value > 1 {
+                        return Err(format!("Invalid Nullable has-value flag {has_value} at {start:#X} for {}", field.name).into())
+                    }
+                    let inner_field = nullable_field(field, inner)?;
+                    let value = RszType::from_field(data, &inner_field, base)?;
+                    let end = start + field.size as u64;
+                    let pos = data.stream_position()?;
+                    if pos > end {
+                        return Err(format!("Nullable {} read past its size, {pos:#X} > {end:#X}", field.name).into())
                     }
+                    data.seek(std::io::SeekFrom::Start(end))?;
+                    let value = if has_value == 1 { value } else { RszType::Null };
+                    x = RszType::Nullable(Box::new(value), inner.to_string())
                 } else {
                     return Err(format!("field original type {:?} not in dump map", field).into())
                 };
@@ -269,6 +261,151 @@ impl RszType {
                 Ok(r#type)
         }
     }
+
+    pub fn write_field<W: Write + Seek>(&self, data: &mut W, field: &RszField) -> Result<()> {
+        data.write_align_up(field.align.into())?;
+        use RszType::*;
+        match self {
+            Null | Extern(_) => (),
+            Int8(v) => data.write_i8(*v)?,
+            Int16(v) => data.write_i16(*v)?,
+            Int32(v) => data.write_i32(*v)?,
+            Int64(v) => data.write_i64(*v)?,
+            UInt8(v) | F8(v) => data.write_u8(*v)?,
+            UInt16(v) | F16(v) => data.write_u16(*v)?,
+            UInt32(v) => data.write_u32(*v)?,
+            UInt64(v) | Size(v) => data.write_u64(*v)?,
+            F32(v) => data.write_f32(*v)?,
+            F64(v) => data.write_f64(*v)?,
+            UInt2(v) => for x in [v.0, v.1] { data.write_u32(x)? },
+            UInt3(v) => for x in [v.0, v.1, v.2] { data.write_u32(x)? },
+            UInt4(v) | Rect(v) => for x in [v.0, v.1, v.2, v.3] { data.write_u32(x)? },
+            Int2(v) | RangeI(v) => for x in [v.0, v.1] { data.write_i32(x)? },
+            Int3(v) => for x in [v.0, v.1, v.2] { data.write_i32(x)? },
+            Int4(v) => for x in [v.0, v.1, v.2, v.3] { data.write_i32(x)? },
+            Color(v) => for x in [v.0, v.1, v.2, v.3] { data.write_u8(x)? },
+            Vec2(v) => {
+                data.write_f32vec2(v)?;
+                data.write_align_up(16)?;
+            },
+            Vec3(v) => {
+                data.write_f32vec3(v)?;
+                data.write_align_up(16)?;
+            },
+            Float2(v) => data.write_f32vec2(v)?,
+            Float3(v) => data.write_f32vec3(v)?,
+            Vec4(v) | Float4(v) => data.write_f32vec4(v)?,
+            Mat4x4(v) => data.write_f32m4x4(v)?,
+            Quaternion(v) | Sphere(v) => for x in [v.0, v.1, v.2, v.3] { data.write_f32(x)? },
+            Position(v) => for x in [v.0, v.1, v.2] { data.write_f32(x)? },
+            Range(v) => for x in [v.0, v.1] { data.write_f32(x)? },
+            AABB(v) => for x in [v.0, v.1, v.2, v.3, v.4, v.5, v.6, v.7] { data.write_f32(x)? },
+            Capsule(v) => for x in [v.0, v.1, v.2] { data.write_f32vec3(&x)? },
+            OBB((coord, extent)) => {
+                data.write_f32m4x4(coord)?;
+                data.write_f32vec3(extent)?;
+                data.write_align_up(16)?;
+            },
+            Guid(v) | GameObjectRef(v) => data.write_all(v)?,
+            Data(v) => data.write_all(v)?,
+            Bool(v) => data.write_bool(*v)?,
+            String(v) => data.write_utf16str(v)?,
+            RuntimeType(v) => {
+                data.write_u32(v.len() as u32)?;
+                data.write_all(v.as_bytes())?;
+            },
+            Object(_, ptr) => data.write_u32(*ptr)?,
+            Struct(v) => RszDump::write_struct(data, v)?,
+            Enum(underlying, _) => underlying.write_field(data, field)?,
+            Array(_) => return Err(format!("Nested array in field {}", field.name).into()),
+            Nullable(value, inner) => {
+                let start = data.stream_position()?;
+                data.write_bool(!matches!(**value, Null))?;
+                if !matches!(**value, Null) {
+                    value.write_field(data, &nullable_field(field, inner)?)?;
+                }
+                let (pos, end) = (data.stream_position()?, start + field.size as u64);
+                if pos < end {
+                    data.write_all(&vec![0; (end - pos) as usize])?;
+                }
+            },
+        }
+        Ok(())
+    }
+}
+
+
+// "System.Nullable`1[[via.vec3, System.Private.CoreLib, Version=1.0.0.0, Culture=neutral, PublicKeyToken=null]]" -> "via.vec3"
+fn nullable_inner_type(original_type: &str) -> Option<&str> {
+    let inner = original_type.strip_prefix("System.Nullable`1[[")?;
+    let mut depth = 0;
+    for (i, c) in inner.char_indices() {
+        match c {
+            '[' | '<' => depth += 1,
+            ']' | '>' if depth > 0 => depth -= 1,
+            ',' | ']' if depth == 0 => return Some(inner[..i].trim()),
+            _ => (),
+        }
+    }
+    None
+}
+
+// Builds the field description of the value inside a Nullable
+fn nullable_field(field: &RszField, inner: &str) -> Result<RszField> {
+    let (r#type, align, size) = match inner {
+        "System.Boolean" => ("Bool", 1, 1),
+        "System.SByte" => ("S8", 1, 1),
+        "System.Byte" => ("U8", 1, 1),
+        "System.Int16" => ("S16", 2, 2),
+        "System.UInt16" => ("U16", 2, 2),
+        "System.Int32" => ("S32", 4, 4),
+        "System.UInt32" => ("U32", 4, 4),
+        "System.Int64" => ("S64", 8, 8),
+        "System.UInt64" => ("U64", 8, 8),
+        "System.Single" => ("F32", 4, 4),
+        "System.Double" => ("F64", 8, 8),
+        "System.Guid" => ("Guid", 8, 16),
+        "via.vec2" => ("Vec2", 16, 16),
+        "via.vec3" => ("Vec3", 16, 16),
+        "via.vec4" => ("Vec4", 16, 16),
+        "via.Quaternion" => ("Quaternion", 16, 16),
+        "via.mat4" => ("Mat4", 16, 64),
+        "via.Float2" => ("Float2", 4, 8),
+        "via.Float3" => ("Float3", 4, 12),
+        "via.Float4" => ("Float4", 4, 16),
+        "via.Int2" => ("Int2", 4, 8),
+        "via.Int3" => ("Int3", 4, 12),
+        "via.Int4" => ("Int4", 4, 16),
+        "via.Uint2" => ("Uint2", 4, 8),
+        "via.Uint3" => ("Uint3", 4, 12),
+        "via.Uint4" => ("Uint4", 4, 16),
+        "via.Color" => ("Color", 4, 4),
+        "via.Range" => ("Range", 4, 8),
+        "via.RangeI" => ("RangeI", 4, 8),
+        "via.Position" => ("Position", 8, 24),
+        "via.Sphere" => ("Sphere", 16, 16),
+        "via.AABB" => ("AABB", 16, 32),
+        "via.Capsule" => ("Capsule", 16, 48),
+        "via.OBB" => ("OBB", 16, 80),
+        "via.Size" => ("Size", 8, 8),
+        // enums are stored as their underlying integer, which is only known from the fields using them
+        _ if enum_map().contains_key(inner) => match RszDump::enum_types().get(inner) {
+            Some(enum_field) => (enum_field.r#type.as_str(), enum_field.align, enum_field.size),
+            None => return Err(format!("No underlying type for Nullable enum {inner} in {}", field.name).into()),
+        },
+        // the fields of a struct align themselves
+        _ if RszDump::name_map().get(&inner.to_string()).is_some() => ("Struct", 1, 0),
+        _ => return Err(format!("Unsupported Nullable value type {inner} in {}", field.name).into()),
+    };
+    Ok(RszField {
+        align,
+        array: false,
+        name: field.name.clone(),
+        native: false,
+        original_type: inner.to_string(),
+        size,
+        r#type: r#type.to_string(),
+    })
 }
 
 
@@ -292,6 +429,13 @@ impl TryInto<String> for RszType {
     }
 }
 
+pub fn serialize_obb<S: serde::Serializer>(obb: &(Mat4x4, Vec3), serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    let mut state = serializer.serialize_struct("OBB", 2)?;
+    state.serialize_field("coord", &obb.0)?;
+    state.serialize_field("extent", &obb.1)?;
+    state.end()
+}
+
 #[derive(Debug, Clone)]
 struct RszSerializerContext<'a> {
     structs: &'a Vec<RszValue>,
@@ -353,10 +497,13 @@ impl<'a> Serialize for RszTypeWithContext<'a> {
             AABB(v) => v.serialize(serializer),
             Capsule(v) => v.serialize(serializer),
             Rect(v) => v.serialize(serializer),
-            Guid(id) => {
+            OBB(v) => serialize_obb(v, serializer),
+            Nullable(value, _) => RszTypeWithContext(value, context).serialize(serializer),
+            Guid(id) | GameObjectRef(id) => {
                 let id = Uuid::from_bytes_le(*id);
                 serializer.serialize_str(&id.to_string().as_str())
             },
+            Null => serializer.serialize_none(),
             RszType::Struct(r#struct) => {
                 let struct_info = RszDump::rsz_map().get(RszDump::name_map().get(&r#struct.name).unwrap()).expect("Could not find struct in dump");
 
@@ -562,7 +709,6 @@ impl<'a> Serialize for RszTypeWithContext<'a> {
                 }
                 state.end()
             }
-            _ => serializer.serialize_str("NOT IMPLEMENTED")
         }
     }
 }
@@ -693,9 +839,11 @@ pub struct RszDump;
 
 
 impl RszDump {
+    // base is added to the data position in error offsets, None leaves offsets to the caller
     pub fn parse_struct<'a, F: 'a + Read + Seek>(
         data: &mut F,
         type_descriptor: TypeDescriptor,
+        base: Option<u64>,
     ) -> Result<RszValue> {
         let struct_type = match RszDump::rsz_map().get(&type_descriptor.hash) {
             Some(x) => x,
@@ -703,29 +851,63 @@ impl RszDump {
         };
 
         //println!("{:?}", struct_type);
+        let field_values = Self::parse_fields(data, struct_type, base).context(Some(struct_type.name.clone()), None)?;
+        //println!("{:?}, {:?}", struct_type, field_values);
+        Ok(RszValue {
+            name: struct_type.name.clone(),
+            crc: struct_type.crc,
+            fields: field_values,
+        })
+    }
+
+    // errors name the field path from this struct down, without the struct's own type name
+    fn parse_fields<F: Read + Seek>(data: &mut F, struct_type: &RszStruct<RszField>, base: Option<u64>) -> Result<Vec<RszType>> {
         let mut field_values = Vec::new();
         for field in &struct_type.fields {
+            let field_path = || Some(field.name.clone());
             if field.array {
-                data.seek_align_up(4)?;
-                let count = data.read_u32()?;
+                let start = data.stream_position()?;
+                data.seek_align_up(4).context(field_path(), base.map(|base| base + start))?;
+                let count = data.read_u32().context(field_path(), base.map(|base| base + start))?;
                 //println!("count: {}, {count}", field.name);
-                let vals = (0..count).map(|_| {
-                    RszType::from_field(data, field)
+                let vals = (0..count).map(|i| {
+                    let start = data.stream_position()?;
+                    RszType::from_field(data, field, base)
+                        .context(Some(format!("{}[{i}]", field.name)), base.map(|base| base + start))
                 }).collect::<Result<Vec<RszType>>>()?;
                 field_values.push(RszType::Array(vals));
             } else {
                 //println!("name: {}", field.name);
-                let r#type = RszType::from_field(data, field)?;
+                let start = data.stream_position()?;
+                let r#type = RszType::from_field(data, field, base).context(field_path(), base.map(|base| base + start))?;
                 //println!("{:?}", r#type);
                 field_values.push(r#type);
             }
         }
-        //println!("{:?}, {:?}", struct_type, field_values);
-        Ok(RszValue {
-            name: struct_type.name.clone(),
-            crc: struct_type.crc,
-            fields: field_values,
-        })
+        Ok(field_values)
+    }
+
+    pub fn write_struct<W: Write + Seek>(data: &mut W, value: &RszValue) -> Result<()> {
+        let struct_type = match value.hash().and_then(|hash| RszDump::rsz_map().get(hash)) {
+            Some(x) => x,
+            None => return Err(format!("Struct {} not in dump map", value.name).into())
+        };
+        if struct_type.fields.len() != value.fields.len() {
+            return Err(format!("Struct {} has {} fields, dump expects {}", value.name, value.fields.len(), struct_type.fields.len()).into())
+        }
+        for (field, v) in struct_type.fields.iter().zip(&value.fields) {
+            match v {
+                RszType::Array(vals) if field.array => {
+                    data.write_align_up(4)?;
+                    data.write_u32(vals.len() as u32)?;
+                    for val in vals {
+                        val.write_field(data, field)?;
+                    }
+                },
+                _ => v.write_field(data, field)?,
+            }
+        }
+        Ok(())
     }
 
     pub fn rsz_map() -> &'static RszMap<RszMapType> {
@@ -762,6 +944,23 @@ impl RszDump {
             RszMap(m)
         })
     }
+
+    // enum name -> a field of that enum, to get the underlying integer type, align and size
+    pub fn enum_types() -> &'static HashMap<String, RszField> {
+        static HASHMAP: OnceLock<HashMap<String, RszField>> = OnceLock::new();
+        HASHMAP.get_or_init(|| {
+            let mut m = HashMap::new();
+            for rsz_struct in Self::rsz_map().0.values() {
+                for field in &rsz_struct.fields {
+                    let name = field.original_type.replace("[]", "");
+                    if field.r#type != "Struct" && enum_map().contains_key(&name) {
+                        m.entry(name).or_insert_with(|| field.clone());
+                    }
+                }
+            }
+            m
+        })
+    }
 }
 
 
@@ -772,6 +971,37 @@ pub struct DeRsz {
     pub extern_idxs: HashSet<u32>,
 }
 
+impl DeRsz {
+    // Rebuilds the instance table and data block, externs only get a slot entry
+    pub fn to_rsz(&self) -> Result<Rsz> {
+        let mut data = Cursor::new(Vec::new());
+        let mut type_descriptors = Vec::new();
+        let mut extern_slots = HashMap::new();
+        for (i, value) in self.structs.iter().enumerate() {
+            let hash = value.hash().copied().unwrap_or(0);
+            type_descriptors.push(TypeDescriptor { hash, crc: value.crc });
+            if self.extern_idxs.contains(&(i as u32)) {
+                let path = match value.fields.first() {
+                    Some(RszType::Extern(path)) => path.clone(),
+                    _ => return Err(format!("Extern slot {i} has no path").into())
+                };
+                extern_slots.insert(i as u32, crate::rsz::Extern { hash, path });
+                continue
+            }
+            if hash != 0 {
+                RszDump::write_struct(&mut data, value)?;
+            }
+        }
+        Ok(Rsz {
+            roots: self.roots.clone(),
+            extern_slots,
+            type_descriptors,
+            data: data.into_inner(),
+            data_offset: 0,
+        })
+    }
+}
+
 impl<'a> Serialize for DeRsz {
     fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
     where
@@ -858,3 +1088,294 @@ pub fn get_enum_name(name: &str, value: &str) -> Option<String> {
     None
 }
 
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // Points RSZ_FILE and ENUM_FILE at a small dump written to the temp dir, every test shares it
+    pub(crate) fn init_dump() {
+        let field = |name: &str, r#type: &str, original_type: &str, align: u32, size: u32, array: bool| json!({
+            "align": align, "array": array, "name": name, "native": false,
+            "original_type": original_type, "size": size, "type": r#type,
+        });
+        let nullable = |inner: &str| format!("System.Nullable`1[[{inner}, System.Private.CoreLib, Version=1.0.0.0, Culture=neutral, PublicKeyToken=null]]");
+        let nullable_vec3 = &nullable("via.vec3");
+        let dump = json!({
+            "0": { "name": "", "crc": "0", "fields": [] },
+            "1001": { "name": "test.Child", "crc": "11", "fields": [field("v", "F32", "System.Single", 4, 4, false)] },
+            "1002": { "name": "test.Values", "crc": "22", "fields": [
+                field("flag", "Bool", "System.Boolean", 1, 1, false),
+                field("i", "S32", "System.Int32", 4, 4, false),
+                field("kind", "S32", "test.Kind", 4, 4, false),
+                field("pos", "Vec3", "via.vec3", 16, 16, false),
+                field("name", "String", "System.String", 4, 8, false),
+                field("some", "Struct", nullable_vec3, 16, 32, false),
+                field("none", "Struct", nullable_vec3, 16, 32, false),
+                field("list", "S32", "System.Int32[]", 4, 4, true),
+                field("child", "Object", "test.Child", 4, 4, false),
+                field("box", "OBB", "via.OBB", 16, 80, false),
+                field("id", "Guid", "System.Guid", 8, 16, false),
+            ] },
+            "1003": { "name": "test.Small", "crc": "33", "fields": [
+                field("kind", "U8", "test.SmallKind", 1, 1, false),
+                field("maybe", "Struct", &nullable("test.SmallKind"), 1, 2, false),
+                field("lone", "Struct", &nullable("test.Lone"), 4, 8, false),
+            ] },
+            "1004": { "name": "test.Outer", "crc": "44", "fields": [
+                field("pad", "U32", "System.UInt32", 4, 4, false),
+                field("inner", "Struct", "test.Pair", 4, 8, false),
+            ] },
+            "1005": { "name": "test.Pair", "crc": "55", "fields": [
+                field("a", "U32", "System.UInt32", 4, 4, false),
+                field("b", "F32", "System.Single", 4, 4, false),
+            ] },
+        });
+        let enums = json!({
+            "test.Kind": { "1": "One", "2": "Two" },
+            "test.SmallKind": { "3": "Three" },
+            "test.Lone": { "0": "Zero" },
+        });
+        RSZ_FILE.get_or_init(|| {
+            let path = std::env::temp_dir().join(format!("mhwsgen_test_rsz_{}.json", std::process::id()));
+            std::fs::write(&path, dump.to_string()).unwrap();
+            path.to_string_lossy().to_string()
+        });
+        ENUM_FILE.get_or_init(|| {
+            let path = std::env::temp_dir().join(format!("mhwsgen_test_enums_{}.json", std::process::id()));
+            std::fs::write(&path, enums.to_string()).unwrap();
+            path.to_string_lossy().to_string()
+        });
+    }
+
+    pub(crate) fn value(name: &str, crc: u32, fields: Vec<RszType>) -> RszValue {
+        RszValue { name: name.to_string(), crc, fields }
+    }
+
+    // null slot, an extern child, an inline child and the root pointing at the inline one
+    fn sample() -> DeRsz {
+        init_dump();
+        let values = value("test.Values", 0x22, vec![
+            RszType::Bool(true),
+            RszType::Int32(-7),
+            RszType::Enum(Box::new(RszType::Int32(2)), "test.Kind".to_string()),
+            RszType::Vec3(Vec3::new(1.0, 2.0, 3.0)),
+            RszType::String("abc\0".to_string()),
+            RszType::Nullable(Box::new(RszType::Vec3(Vec3::new(4.0, 5.0, 6.0))), "via.vec3".to_string()),
+            RszType::Nullable(Box::new(RszType::Null), "via.vec3".to_string()),
+            RszType::Array(vec![RszType::Int32(1), RszType::Int32(2), RszType::Int32(3)]),
+            RszType::Object(RszDump::rsz_map().get(&0x1001).unwrap().clone(), 2),
+            RszType::OBB((Mat4x4::identity(), Vec3::new(0.5, 1.5, 2.5))),
+            RszType::Guid(std::array::from_fn(|i| i as u8)),
+        ]);
+        DeRsz {
+            roots: vec![3],
+            structs: vec![
+                value("", 0, vec![]),
+                value("test.Child", 0x11, vec![RszType::Extern("test/extern.user".to_string())]),
+                value("test.Child", 0x11, vec![RszType::F32(0.25)]),
+                values,
+            ],
+            extern_idxs: HashSet::from([1]),
+        }
+    }
+
+    #[test]
+    fn rsz_round_trip() {
+        let mut block = Cursor::new(Vec::new());
+        sample().to_rsz().unwrap().write(&mut block).unwrap();
+        let rsz = Rsz::new(&mut block, 0, 0).unwrap();
+        assert_eq!(rsz.roots, vec![3]);
+        assert_eq!(rsz.extern_slots[&1].path, "test/extern.user");
+        rsz.check_rebuild().unwrap();
+
+        let dersz = rsz.deserializev2(None).unwrap();
+        let fields = &dersz.structs[3].fields;
+        assert!(matches!(&fields[4], RszType::String(s) if s == "abc\0"));
+        assert!(matches!(&fields[5], RszType::Nullable(v, _) if matches!(**v, RszType::Vec3(v) if v == Vec3::new(4.0, 5.0, 6.0))));
+        assert!(matches!(&fields[6], RszType::Nullable(v, _) if matches!(**v, RszType::Null)));
+        assert!(matches!(&fields[8], RszType::Object(_, 2)));
+        assert!(matches!(&fields[9], RszType::OBB((_, extent)) if *extent == Vec3::new(0.5, 1.5, 2.5)));
+    }
+
+    // a u8 has-value flag, then the vec3 at its own 16 alignment, the rest of the field is zero
+    #[test]
+    fn nullable_bytes() {
+        init_dump();
+        let field = RszDump::rsz_map().get(&0x1002).unwrap().fields[5].clone();
+        let mut present = vec![1u8];
+        present.resize(16, 0);
+        for x in [4.0f32, 5.0, 6.0, 0.0] {
+            present.extend(x.to_le_bytes());
+        }
+        let null = vec![0u8; 32];
+
+        let cases = [
+            (RszType::Nullable(Box::new(RszType::Vec3(Vec3::new(4.0, 5.0, 6.0))), "via.vec3".to_string()), present),
+            (RszType::Nullable(Box::new(RszType::Null), "via.vec3".to_string()), null),
+        ];
+        for (value, bytes) in cases {
+            let mut data = Cursor::new(Vec::new());
+            value.write_field(&mut data, &field).unwrap();
+            assert_eq!(data.get_ref(), &bytes);
+
+            let mut data = Cursor::new(&bytes);
+            let read = RszType::from_field(&mut data, &field, None).unwrap();
+            assert_eq!(data.position(), 32);
+            assert_eq!(format!("{read:?}"), format!("{value:?}"));
+        }
+
+        let mut bad = vec![2u8];
+        bad.resize(32, 0);
+        assert!(RszType::from_field(&mut Cursor::new(&bad), &field, None).is_err());
+    }
+
+    // the nullable takes the u8 of the enum's other field, a one byte flag and the value right after it
+    #[test]
+    fn nullable_enum_underlying_type() {
+        init_dump();
+        let fields = &RszDump::rsz_map().get(&0x1003).unwrap().fields;
+        let value = RszType::from_field(&mut Cursor::new(&[1u8, 3]), &fields[1], None).unwrap();
+        assert_eq!(
+            format!("{value:?}"),
+            format!("{:?}", RszType::Nullable(
+                Box::new(RszType::Enum(Box::new(RszType::UInt8(3)), "test.SmallKind".to_string())),
+                "test.SmallKind".to_string(),
+            )),
+        );
+        let mut data = Cursor::new(Vec::new());
+        value.write_field(&mut data, &fields[1]).unwrap();
+        assert_eq!(data.get_ref(), &[1, 3]);
+
+        // no field stores test.Lone directly, so its size is unknown
+        let err = RszType::from_field(&mut Cursor::new(&[0u8; 8]), &fields[2], None).unwrap_err();
+        assert!(err.to_string().contains("No underlying type for Nullable enum test.Lone"), "{err}");
+    }
+
+    // every type string from_field knows, read from raw bytes and serialized in the context of sample()
+    #[test]
+    fn parse_and_serialize_every_type() {
+        let dersz = sample();
+        let context = RszSerializerContext { structs: &dersz.structs, parent_ptr: 3 };
+        let f32s = |v: &[f32]| v.iter().flat_map(|x| x.to_le_bytes()).collect::<Vec<u8>>();
+        let i32s = |v: &[i32]| v.iter().flat_map(|x| x.to_le_bytes()).collect::<Vec<u8>>();
+        let padded = |mut v: Vec<u8>, len: usize| { v.resize(len, 0); v };
+        let utf16 = |s: &str| {
+            let units: Vec<u16> = s.encode_utf16().collect();
+            let mut v = (units.len() as u32).to_le_bytes().to_vec();
+            v.extend(units.iter().flat_map(|c| c.to_le_bytes()));
+            v
+        };
+        let mat: Vec<f32> = (1..=16).map(|x| x as f32).collect();
+        let mat_json = "[1.0,2.0,3.0,4.0,5.0,6.0,7.0,8.0,9.0,10.0,11.0,12.0,13.0,14.0,15.0,16.0]";
+        let guid: Vec<u8> = (0..16).collect();
+        let guid_json = "\"03020100-0504-0706-0809-0a0b0c0d0e0f\"";
+        let nullable_vec3 = "System.Nullable`1[[via.vec3, System.Private.CoreLib, Version=1.0.0.0, Culture=neutral, PublicKeyToken=null]]";
+
+        // (type, original type, align, size, bytes, json)
+        type Case<'a> = (&'a str, &'a str, u32, u32, Vec<u8>, String);
+        let cases: Vec<Case> = vec![
+            ("S8", "System.SByte", 1, 1, vec![0xFF], "-1".into()),
+            ("S16", "System.Int16", 2, 2, (-2i16).to_le_bytes().to_vec(), "-2".into()),
+            ("S32", "System.Int32", 4, 4, i32s(&[-3]), "-3".into()),
+            ("S64", "System.Int64", 8, 8, (-4i64).to_le_bytes().to_vec(), "-4".into()),
+            ("U8", "System.Byte", 1, 1, vec![5], "5".into()),
+            ("U16", "System.UInt16", 2, 2, 6u16.to_le_bytes().to_vec(), "6".into()),
+            ("U32", "System.UInt32", 4, 4, 7u32.to_le_bytes().to_vec(), "7".into()),
+            ("U64", "System.UInt64", 8, 8, u64::MAX.to_le_bytes().to_vec(), u64::MAX.to_string()),
+            ("F8", "via.f8", 1, 1, vec![9], "9".into()),
+            ("F16", "via.f16", 2, 2, 0x3C00u16.to_le_bytes().to_vec(), "15360".into()),
+            ("F32", "System.Single", 4, 4, f32s(&[0.5]), "0.5".into()),
+            ("F64", "System.Double", 8, 8, 0.25f64.to_le_bytes().to_vec(), "0.25".into()),
+            ("Uint2", "via.Uint2", 4, 8, i32s(&[1, 2]), "[1,2]".into()),
+            ("Uint3", "via.Uint3", 4, 12, i32s(&[1, 2, 3]), "[1,2,3]".into()),
+            ("Uint4", "via.Uint4", 4, 16, i32s(&[1, 2, 3, 4]), "[1,2,3,4]".into()),
+            ("Color", "via.Color", 4, 4, vec![1, 2, 3, 4], "[1,2,3,4]".into()),
+            ("Int2", "via.Int2", 4, 8, i32s(&[-1, 2]), "[-1,2]".into()),
+            ("Int3", "via.Int3", 4, 12, i32s(&[-1, 2, 3]), "[-1,2,3]".into()),
+            ("Int4", "via.Int4", 4, 16, i32s(&[-1, 2, 3, 4]), "[-1,2,3,4]".into()),
+            ("Vec2", "via.vec2", 16, 16, padded(f32s(&[1.0, 2.0]), 16), "[1.0,2.0]".into()),
+            ("Vec3", "via.vec3", 16, 16, padded(f32s(&[1.0, 2.0, 3.0]), 16), "[1.0,2.0,3.0]".into()),
+            ("Vec4", "via.vec4", 16, 16, f32s(&[1.0, 2.0, 3.0, 4.0]), "[1.0,2.0,3.0,4.0]".into()),
+            ("Quaternion", "via.Quaternion", 16, 16, f32s(&[0.0, 0.0, 0.0, 1.0]), "[0.0,0.0,0.0,1.0]".into()),
+            ("Sphere", "via.Sphere", 16, 16, f32s(&[1.0, 2.0, 3.0, 4.0]), "[1.0,2.0,3.0,4.0]".into()),
+            ("Position", "via.Position", 8, 24, f32s(&[1.0, 2.0, 3.0]), "[1.0,2.0,3.0]".into()),
+            ("Float2", "via.Float2", 4, 8, f32s(&[1.0, 2.0]), "[1.0,2.0]".into()),
+            ("Float3", "via.Float3", 4, 12, f32s(&[1.0, 2.0, 3.0]), "[1.0,2.0,3.0]".into()),
+            ("Float4", "via.Float4", 4, 16, f32s(&[1.0, 2.0, 3.0, 4.0]), "[1.0,2.0,3.0,4.0]".into()),
+            ("Mat4", "via.mat4", 16, 64, f32s(&mat), mat_json.into()),
+            ("Range", "via.Range", 4, 8, f32s(&[0.0, 1.5]), "[0.0,1.5]".into()),
+            ("RangeI", "via.RangeI", 4, 8, i32s(&[-1, 1]), "[-1,1]".into()),
+            ("Data", "via.Data", 1, 3, vec![1, 2, 3], "[1,2,3]".into()),
+            ("AABB", "via.AABB", 16, 32, f32s(&[0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0]),
+                "[0.0,0.0,0.0,0.0,1.0,1.0,1.0,1.0]".into()),
+            ("Capsule", "via.Capsule", 16, 48, f32s(&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 0.5, 0.0, 0.0]),
+                "[[1.0,2.0,3.0],[4.0,5.0,6.0],[0.5,0.0,0.0]]".into()),
+            ("Rect", "via.Rect", 4, 16, i32s(&[0, 0, 1, 1]), "[0,0,1,1]".into()),
+            ("OBB", "via.OBB", 16, 80, padded([f32s(&mat), f32s(&[0.5, 1.5, 2.5])].concat(), 80),
+                format!(r#"{{"coord":{mat_json},"extent":[0.5,1.5,2.5]}}"#)),
+            ("Guid", "System.Guid", 8, 16, guid.clone(), guid_json.into()),
+            ("GameObjectRef", "via.GameObjectRef", 8, 16, guid.clone(), guid_json.into()),
+            ("Bool", "System.Boolean", 1, 1, vec![1], "true".into()),
+            ("String", "System.String", 4, 8, utf16("a\"<b>\0"), r#""a\"<b>\u0000""#.into()),
+            ("Resource", "via.resource", 4, 8, utf16("x/y.user\0"), r#""x/y.user\u0000""#.into()),
+            ("Struct", "test.Child", 4, 4, f32s(&[0.25]), r#"{"v":0.25}"#.into()),
+            ("Struct", nullable_vec3, 16, 32, padded([padded(vec![1], 16), f32s(&[4.0, 5.0, 6.0])].concat(), 32),
+                "[4.0,5.0,6.0]".into()),
+            ("Struct", nullable_vec3, 16, 32, vec![0; 32], "null".into()),
+            ("Size", "System.UInt64", 8, 8, 10u64.to_le_bytes().to_vec(), "10".into()),
+            ("RuntimeType", "System.Type", 4, 4, [4u32.to_le_bytes().to_vec(), b"via.".to_vec()].concat(), "\"via.\"".into()),
+            ("Object", "test.Child", 4, 4, 2u32.to_le_bytes().to_vec(), r#"{"v":0.25}"#.into()),
+            ("UserData", "test.Child", 4, 4, 1u32.to_le_bytes().to_vec(), r#"{"test.Child":"test/extern.user"}"#.into()),
+            ("S32", "test.Kind", 4, 4, i32s(&[2]), "\"Two\"".into()),
+        ];
+        for (r#type, original_type, align, size, bytes, expected) in cases {
+            let field = RszField {
+                align, array: false, name: "f".to_string(), native: false,
+                original_type: original_type.to_string(), size, r#type: r#type.to_string(),
+            };
+            let mut data = Cursor::new(&bytes);
+            let value = RszType::from_field(&mut data, &field, None)
+                .unwrap_or_else(|e| panic!("{type} {original_type} failed to parse: {e}"));
+            assert_eq!(data.position(), bytes.len() as u64, "{type} {original_type} read length");
+            let json = serde_json::to_string(&RszTypeWithContext(&value, &context))
+                .unwrap_or_else(|e| panic!("{type} {original_type} failed to serialize: {e}"));
+            assert_eq!(json, expected, "{type} {original_type}");
+        }
+
+        let field = RszField {
+            align: 4, array: false, name: "f".to_string(), native: false,
+            original_type: "via.Unknown".to_string(), size: 4, r#type: "Unknown".to_string(),
+        };
+        assert!(RszType::from_field(&mut Cursor::new(&[0u8; 4]), &field, None).is_err());
+    }
+
+    // the path goes through field names only and the offset is the inner field's, not the struct's
+    #[test]
+    fn nested_struct_error_context() {
+        init_dump();
+        let mut data = Cursor::new(vec![0u8; 10]);
+        let err = RszDump::parse_struct(&mut data, TypeDescriptor { hash: 0x1004, crc: 0x44 }, Some(0x100)).unwrap_err();
+        match err.downcast_ref::<crate::reerr::FileParseError>() {
+            Some(crate::reerr::FileParseError::Context { offset, field, source, .. }) => {
+                assert_eq!(field.as_deref(), Some("test.Outer.inner.b"));
+                assert_eq!(*offset, Some(0x108));
+                let source = source.downcast_ref::<std::io::Error>().expect("io error source");
+                assert_eq!(source.kind(), std::io::ErrorKind::UnexpectedEof);
+            },
+            _ => panic!("expected a Context error, got {err}"),
+        }
+    }
+
+    #[test]
+    fn rebuild_reports_changed_data() {
+        let mut block = Cursor::new(Vec::new());
+        sample().to_rsz().unwrap().write(&mut block).unwrap();
+        let mut rsz = Rsz::new(&mut block, 0, 0).unwrap();
+        // the u8 after the root's bool is alignment padding the writer always zeroes
+        rsz.data[5] = 1;
+        let err = rsz.check_rebuild().unwrap_err().to_string();
+        assert!(err.contains(&format!("0x{:08X}", rsz.data_offset + 5)), "{err}");
+    }
+}
@@ -3,7 +3,7 @@ use crate::reerr::FileParseError;
 use nalgebra_glm::*;
 use std::convert::TryInto;
 use std::error::Error;
-use std::io::{Read, Seek};
+use std::io::{Read, Seek, Write};
 
 type Result<T> = std::result::Result<T, Box<dyn Error>>;
 
@@ -170,11 +170,7 @@ impl<T: Seek + Read + ?Sized> SeekExt for T {
     fn seek_noop(&mut self, from_start: u64) -> Result<u64> {
         let pos = self.stream_position()?;
         if pos != from_start {
-            assert_eq!(
-                pos,
-                from_start,
-                "This seek is expected to be no-op. At 0x{pos:08X}, seeking to 0x{from_start:08X}",
-            );
+            return Err(Box::new(FileParseError::UnexpectedSeek { pos, expected: from_start }))
         }
         Ok(pos)
     }
@@ -182,11 +178,7 @@ impl<T: Seek + Read + ?Sized> SeekExt for T {
     fn seek_assert_align_up(&mut self, from_start: u64, align: u64) -> Result<u64> {
         let pos = self.stream_position()?;
         if align_up(pos, align) != from_start {
-            assert_eq!(
-                align_up(pos, align),
-                from_start,
-                "This seek is expected to only align up {align}. At 0x{pos:08X}, seeking to 0x{from_start:08X}",
-            );
+            return Err(Box::new(FileParseError::UnexpectedAlign { pos, expected: from_start, align }))
         }
         if pos != from_start {
             let mut buf = vec![0; (from_start - pos).try_into()?];
@@ -217,3 +209,112 @@ impl<T: Seek + Read + ?Sized> SeekExt for T {
         Ok(self.stream_position()?)
     }
 }
+
+pub trait WriteExt {
+    fn write_bool(&mut self, v: bool) -> Result<()>;
+    fn write_u8(&mut self, v: u8) -> Result<()>;
+    fn write_u16(&mut self, v: u16) -> Result<()>;
+    fn write_u32(&mut self, v: u32) -> Result<()>;
+    fn write_u64(&mut self, v: u64) -> Result<()>;
+    fn write_i8(&mut self, v: i8) -> Result<()>;
+    fn write_i16(&mut self, v: i16) -> Result<()>;
+    fn write_i32(&mut self, v: i32) -> Result<()>;
+    fn write_i64(&mut self, v: i64) -> Result<()>;
+    fn write_f32(&mut self, v: f32) -> Result<()>;
+    fn write_f64(&mut self, v: f64) -> Result<()>;
+    fn write_u16str(&mut self, v: &str) -> Result<()>;
+    fn write_utf16str(&mut self, v: &str) -> Result<()>;
+    fn write_f32vec2(&mut self, v: &Vec2) -> Result<()>;
+    fn write_f32vec3(&mut self, v: &Vec3) -> Result<()>;
+    fn write_f32vec4(&mut self, v: &Vec4) -> Result<()>;
+    fn write_f32m4x4(&mut self, v: &Mat4x4) -> Result<()>;
+    fn write_align_up(&mut self, align: u64) -> Result<u64>;
+}
+
+impl<T: Write + Seek + ?Sized> WriteExt for T {
+    fn write_bool(&mut self, v: bool) -> Result<()> {
+        self.write_u8(v as u8)
+    }
+    fn write_u8(&mut self, v: u8) -> Result<()> {
+        Ok(self.write_all(&[v])?)
+    }
+    fn write_u16(&mut self, v: u16) -> Result<()> {
+        Ok(self.write_all(&v.to_le_bytes())?)
+    }
+    fn write_u32(&mut self, v: u32) -> Result<()> {
+        Ok(self.write_all(&v.to_le_bytes())?)
+    }
+    fn write_u64(&mut self, v: u64) -> Result<()> {
+        Ok(self.write_all(&v.to_le_bytes())?)
+    }
+    fn write_i8(&mut self, v: i8) -> Result<()> {
+        Ok(self.write_all(&v.to_le_bytes())?)
+    }
+    fn write_i16(&mut self, v: i16) -> Result<()> {
+        Ok(self.write_all(&v.to_le_bytes())?)
+    }
+    fn write_i32(&mut self, v: i32) -> Result<()> {
+        Ok(self.write_all(&v.to_le_bytes())?)
+    }
+    fn write_i64(&mut self, v: i64) -> Result<()> {
+        Ok(self.write_all(&v.to_le_bytes())?)
+    }
+    fn write_f32(&mut self, v: f32) -> Result<()> {
+        Ok(self.write_all(&v.to_le_bytes())?)
+    }
+    fn write_f64(&mut self, v: f64) -> Result<()> {
+        Ok(self.write_all(&v.to_le_bytes())?)
+    }
+
+    fn write_u16str(&mut self, v: &str) -> Result<()> {
+        for c in v.encode_utf16() {
+            self.write_u16(c)?;
+        }
+        self.write_u16(0)
+    }
+
+    // counterpart of read_utf16str, the count is written as is so a trailing null survives a round trip
+    fn write_utf16str(&mut self, v: &str) -> Result<()> {
+        let s: Vec<u16> = v.encode_utf16().collect();
+        self.write_u32(s.len() as u32)?;
+        for c in s {
+            self.write_u16(c)?;
+        }
+        Ok(())
+    }
+
+    fn write_f32vec2(&mut self, v: &Vec2) -> Result<()> {
+        self.write_f32(v.x)?;
+        self.write_f32(v.y)
+    }
+
+    fn write_f32vec3(&mut self, v: &Vec3) -> Result<()> {
+        self.write_f32(v.x)?;
+        self.write_f32(v.y)?;
+        self.write_f32(v.z)
+    }
+
+    fn write_f32vec4(&mut self, v: &Vec4) -> Result<()> {
+        self.write_f32(v.x)?;
+        self.write_f32(v.y)?;
+        self.write_f32(v.z)?;
+        self.write_f32(v.w)
+    }
+
+    fn write_f32m4x4(&mut self, v: &Mat4x4) -> Result<()> {
+        // make_mat4x4 fills column major, so write it back in the same order
+        for x in v.iter() {
+            self.write_f32(*x)?;
+        }
+        Ok(())
+    }
+
+    fn write_align_up(&mut self, align: u64) -> Result<u64> {
+        let pos = self.stream_position()?;
+        let aligned = align_up(pos, align);
+        if aligned != pos {
+            self.write_all(&vec![0; (aligned - pos).try_into()?])?;
+        }
+        Ok(aligned)
+    }
+}
@@ -0,0 +1,240 @@
+// GDeflate writer, makes the tile streams that libdeflater::TileStream/GDeflateDecompressor read.
+// Tiles are single fixed huffman deflate blocks with the symbols spread round robin over 32
+// lanes, and each lane's bits go out as 32 bit words in the order a decoder refills them.
+
+use std::collections::VecDeque;
+
+const TILE_SIZE: usize = 64 * 1024;
+const GDEFLATE_ID: u8 = 4;
+const LANES: usize = 32;
+
+const WINDOW: usize = 32 * 1024;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const MAX_CHAIN: usize = 64;
+const HASH_BITS: usize = 15;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049,
+    3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+
+enum Symbol {
+    Literal(u8),
+    Match { length: usize, distance: usize },
+    EndOfBlock,
+}
+
+// (code, bit count) of a fixed huffman literal/length symbol, already reversed for lsb first writing
+fn fixed_litlen(symbol: usize) -> (u32, u32) {
+    let (code, len) = match symbol {
+        0..=143 => (0x30 + symbol as u32, 8),
+        144..=255 => (0x190 + symbol as u32 - 144, 9),
+        256..=279 => (symbol as u32 - 256, 7),
+        _ => (0xC0 + symbol as u32 - 280, 8),
+    };
+    (code.reverse_bits() >> (32 - len), len)
+}
+
+struct LaneWriter {
+    words: Vec<u32>,
+    bits: [u64; LANES],
+    counts: [u32; LANES],
+    slots: [VecDeque<usize>; LANES],
+}
+
+impl LaneWriter {
+    fn new() -> Self {
+        Self {
+            words: vec![0; LANES],
+            bits: [0; LANES],
+            counts: [0; LANES],
+            slots: std::array::from_fn(|i| VecDeque::from([i])),
+        }
+    }
+
+    fn put(&mut self, lane: usize, value: u32, count: u32) {
+        self.bits[lane] |= (value as u64) << self.counts[lane];
+        self.counts[lane] += count;
+    }
+
+    // decoders refill each lane in order after every round of symbols when it has less than a
+    // word left, so a lane takes the next free slot as soon as it starts on its current word
+    fn flush(&mut self) {
+        for lane in 0..LANES {
+            if self.counts[lane] >= 32 {
+                let slot = self.slots[lane].pop_front().unwrap();
+                self.words[slot] = self.bits[lane] as u32;
+                self.bits[lane] >>= 32;
+                self.counts[lane] -= 32;
+            }
+            let reserved = if self.counts[lane] > 0 { 2 } else { 1 };
+            while self.slots[lane].len() < reserved {
+                self.slots[lane].push_back(self.words.len());
+                self.words.push(0);
+            }
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        for lane in 0..LANES {
+            self.words[self.slots[lane][0]] = self.bits[lane] as u32;
+        }
+        self.words.iter().flat_map(|word| word.to_le_bytes()).collect()
+    }
+}
+
+// greedy hash chain matching, at most 32 bits per symbol with the fixed codes
+fn symbols(data: &[u8]) -> Vec<Symbol> {
+    let hash = |i: usize| {
+        let v = data[i] as usize | (data[i + 1] as usize) << 8 | (data[i + 2] as usize) << 16;
+        (v.wrapping_mul(0x9E3779B1) >> 16) & ((1 << HASH_BITS) - 1)
+    };
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut prev = vec![usize::MAX; data.len()];
+    let insert = |i: usize, head: &mut [usize], prev: &mut [usize]| {
+        if i + MIN_MATCH <= data.len() {
+            let h = hash(i);
+            prev[i] = head[h];
+            head[h] = i;
+        }
+    };
+
+    let mut symbols = vec![];
+    let mut i = 0;
+    while i < data.len() {
+        let mut best = (0, 0);
+        if i + MIN_MATCH <= data.len() {
+            let max = usize::min(MAX_MATCH, data.len() - i);
+            let mut candidate = head[hash(i)];
+            let mut chain = 0;
+            while candidate != usize::MAX && i - candidate <= WINDOW && chain < MAX_CHAIN {
+                let length = (0..max).take_while(|&k| data[candidate + k] == data[i + k]).count();
+                if length > best.0 {
+                    best = (length, i - candidate);
+                    if length == max {
+                        break;
+                    }
+                }
+                candidate = prev[candidate];
+                chain += 1;
+            }
+        }
+        if best.0 >= MIN_MATCH {
+            for k in i..i + best.0 {
+                insert(k, &mut head, &mut prev);
+            }
+            symbols.push(Symbol::Match { length: best.0, distance: best.1 });
+            i += best.0;
+        } else {
+            insert(i, &mut head, &mut prev);
+            symbols.push(Symbol::Literal(data[i]));
+            i += 1;
+        }
+    }
+    symbols.push(Symbol::EndOfBlock);
+    symbols
+}
+
+fn compress_tile(data: &[u8]) -> Vec<u8> {
+    let mut writer = LaneWriter::new();
+    // BFINAL and BTYPE 01 (fixed huffman) go to the first lane
+    writer.put(0, 0b011, 3);
+    for (round, chunk) in symbols(data).chunks(LANES).enumerate() {
+        for (lane, symbol) in chunk.iter().enumerate() {
+            match *symbol {
+                Symbol::Literal(byte) => {
+                    let (code, len) = fixed_litlen(byte as usize);
+                    writer.put(lane, code, len);
+                }
+                Symbol::EndOfBlock => {
+                    let (code, len) = fixed_litlen(256);
+                    writer.put(lane, code, len);
+                }
+                Symbol::Match { length, distance } => {
+                    let l = LENGTH_BASE.iter().rposition(|&base| base as usize <= length).unwrap();
+                    let (code, len) = fixed_litlen(257 + l);
+                    writer.put(lane, code, len);
+                    writer.put(lane, (length - LENGTH_BASE[l] as usize) as u32, LENGTH_EXTRA[l] as u32);
+                    let d = DIST_BASE.iter().rposition(|&base| base as usize <= distance).unwrap();
+                    writer.put(lane, (d as u32).reverse_bits() >> 27, 5);
+                    writer.put(lane, (distance - DIST_BASE[d] as usize) as u32, DIST_EXTRA[d] as u32);
+                }
+            }
+        }
+        // the header shares the first round with the first symbol, which is always a literal
+        debug_assert!(round > 0 || writer.counts[0] <= 32);
+        writer.flush();
+    }
+    writer.finish()
+}
+
+// Tile stream: id, magic, tile count, tile size index and last tile size, then a u32 per tile
+// with the compressed size of the last tile first and the offsets of the others after it
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let tiles = data.chunks(TILE_SIZE).map(compress_tile).collect::<Vec<_>>();
+    let last_tile_size = (data.len() % TILE_SIZE) as u32;
+
+    let mut out = vec![GDEFLATE_ID, GDEFLATE_ID ^ 0xFF];
+    out.extend((tiles.len() as u16).to_le_bytes());
+    out.extend((1 | last_tile_size << 2).to_le_bytes());
+    let mut offset = 0;
+    for (i, tile) in tiles.iter().enumerate() {
+        let entry = if i == 0 { tiles.last().map_or(0, |tile| tile.len()) } else { offset };
+        out.extend((entry as u32).to_le_bytes());
+        offset += tile.len();
+    }
+    for tile in tiles {
+        out.extend(tile);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libdeflater::{GDeflateDecompressor, TileStream};
+    use std::io::Cursor;
+
+    fn round_trip(data: &[u8]) {
+        let compressed = compress(data);
+        let header = TileStream::from(&mut Cursor::new(&compressed)).unwrap();
+        assert_eq!(header.get_uncompressed_size(), data.len());
+        let mut out = vec![0; data.len()];
+        let size = GDeflateDecompressor::gdeflate_decompress(&compressed, &mut out).unwrap();
+        assert_eq!(size, data.len());
+        assert!(out == data, "{} bytes didn't decompress back", data.len());
+    }
+
+    #[test]
+    fn random_data() {
+        let mut state = 0x2545F491u32;
+        let data = (0..5000).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        }).collect::<Vec<_>>();
+        round_trip(&data);
+    }
+
+    #[test]
+    fn zero_data() {
+        round_trip(&[0; 1]);
+        round_trip(&vec![0; 4096]);
+        round_trip(&vec![0; TILE_SIZE]);
+    }
+
+    #[test]
+    fn multiple_tiles() {
+        let data = (0..TILE_SIZE * 5 / 2).map(|i| (i / 7 % 251) as u8 ^ (i >> 12) as u8).collect::<Vec<_>>();
+        assert_eq!(data.chunks(TILE_SIZE).count(), 3);
+        round_trip(&data);
+    }
+}
@@ -0,0 +1,175 @@
+use crate::mot::{Mot, MotBone, TrackKind};
+use crate::reerr::Result;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+
+// Minimal glTF 2.0 builder, everything goes into one external .bin buffer
+struct GltfBuilder {
+    bin: Vec<u8>,
+    buffer_views: Vec<Value>,
+    accessors: Vec<Value>,
+}
+
+impl GltfBuilder {
+    fn new() -> GltfBuilder {
+        GltfBuilder {
+            bin: Vec::new(),
+            buffer_views: Vec::new(),
+            accessors: Vec::new(),
+        }
+    }
+
+    fn push_floats(&mut self, data: &[f32], components: usize, min_max: bool) -> usize {
+        let offset = self.bin.len();
+        for v in data {
+            self.bin.extend_from_slice(&v.to_le_bytes());
+        }
+        self.buffer_views.push(json!({
+            "buffer": 0,
+            "byteOffset": offset,
+            "byteLength": data.len() * 4,
+        }));
+        let ty = match components {
+            1 => "SCALAR",
+            3 => "VEC3",
+            _ => "VEC4",
+        };
+        let mut accessor = json!({
+            "bufferView": self.buffer_views.len() - 1,
+            "componentType": 5126,
+            "count": data.len() / components,
+            "type": ty,
+        });
+        // input accessors of animation samplers must have bounds
+        if min_max {
+            let min = data.iter().cloned().fold(f32::INFINITY, f32::min);
+            let max = data.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+            accessor["min"] = json!([min]);
+            accessor["max"] = json!([max]);
+        }
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+}
+
+fn skeleton_nodes(skeleton: &[MotBone]) -> (Vec<Value>, Vec<usize>) {
+    let mut nodes: Vec<Value> = skeleton.iter().map(|bone| json!({
+        "name": bone.name,
+        "translation": bone.translation,
+        "rotation": bone.rotation,
+    })).collect();
+    let mut roots = Vec::new();
+    for (i, bone) in skeleton.iter().enumerate() {
+        match bone.parent {
+            Some(parent) if parent < skeleton.len() => {
+                let children = nodes[parent]
+                    .as_object_mut()
+                    .unwrap()
+                    .entry("children")
+                    .or_insert_with(|| json!([]));
+                children.as_array_mut().unwrap().push(json!(i));
+            },
+            _ => roots.push(i),
+        }
+    }
+    (nodes, roots)
+}
+
+fn animation(builder: &mut GltfBuilder, skeleton: &[MotBone], mot: &Mot) -> Value {
+    let frame_rate = if mot.frame_rate == 0 { 60.0 } else { mot.frame_rate as f32 };
+    let mut samplers = Vec::new();
+    let mut channels = Vec::new();
+    for clip in &mot.clips {
+        let node = match skeleton.iter().position(|bone| bone.hash == clip.bone_hash) {
+            Some(node) => node,
+            None if (clip.bone_index as usize) < skeleton.len() => clip.bone_index as usize,
+            None => {
+                eprintln!("[WARNING] {}: no bone for hash {:08X}", mot.name, clip.bone_hash);
+                continue
+            }
+        };
+        for track in &clip.tracks {
+            if track.values.is_empty() {
+                continue
+            }
+            let times: Vec<f32> = track.frames.iter().map(|f| f / frame_rate).collect();
+            let (path, components) = match track.kind {
+                TrackKind::Translation => ("translation", 3),
+                TrackKind::Rotation => ("rotation", 4),
+                TrackKind::Scale => ("scale", 3),
+            };
+            let values: Vec<f32> = track.values.iter().flat_map(|v| v[..components].to_vec()).collect();
+            let input = builder.push_floats(&times, 1, true);
+            let output = builder.push_floats(&values, components, false);
+            samplers.push(json!({
+                "input": input,
+                "output": output,
+                "interpolation": "LINEAR",
+            }));
+            channels.push(json!({
+                "sampler": samplers.len() - 1,
+                "target": { "node": node, "path": path },
+            }));
+        }
+    }
+    let mut animation = json!({
+        "name": mot.name,
+        "samplers": samplers,
+        "channels": channels,
+    });
+    if !mot.events.is_empty() {
+        animation["extras"] = json!({ "event_clips": event_clips(mot, frame_rate) });
+    }
+    animation
+}
+
+// glTF has no events, so each clip's keyed properties go in the animation extras with times in seconds
+fn event_clips(mot: &Mot, frame_rate: f32) -> Vec<Value> {
+    mot.events.iter().map(|clip| {
+        let events: Vec<Value> = clip.tracks.iter().flat_map(|track| {
+            track.properties.iter().map(|property| json!({
+                "track": track.name,
+                "property": property.name,
+                "type": property.property_type,
+                "start": property.start_frame / frame_rate,
+                "end": property.end_frame / frame_rate,
+                "keys": property.keys.iter().map(|key| json!({
+                    "time": key.frame / frame_rate,
+                    "interpolation": key.interpolation,
+                    "value": key.value,
+                })).collect::<Vec<_>>(),
+            }))
+        }).collect();
+        json!({
+            "version": clip.version,
+            "duration": clip.frame_count / frame_rate,
+            "events": events,
+        })
+    }).collect()
+}
+
+// Writes a .gltf next to a .bin of the same stem. Bones are emitted as plain nodes named after
+// the engine joints so they line up with the skeleton of an exported mesh.
+pub fn save_animations(skeleton: &[MotBone], mots: &[&Mot], output_path: &Path) -> Result<()> {
+    let mut builder = GltfBuilder::new();
+    let (nodes, roots) = skeleton_nodes(skeleton);
+    let animations: Vec<Value> = mots.iter().map(|mot| animation(&mut builder, skeleton, mot)).collect();
+
+    let bin_path = output_path.with_extension("bin");
+    let bin_name = bin_path.file_name().unwrap().to_string_lossy().to_string();
+    let gltf = json!({
+        "asset": { "version": "2.0", "generator": "mhwsgen" },
+        "scene": 0,
+        "scenes": [{ "nodes": roots }],
+        "nodes": nodes,
+        "animations": animations,
+        "buffers": [{ "uri": bin_name, "byteLength": builder.bin.len() }],
+        "bufferViews": builder.buffer_views,
+        "accessors": builder.accessors,
+    });
+
+    fs::write(&bin_path, &builder.bin)?;
+    fs::write(output_path, serde_json::to_string_pretty(&gltf)?)?;
+    Ok(())
+}
@@ -1,32 +1,47 @@
 mod align;
+mod bench;
 mod reerr;
 mod bitfield;
 mod byte_reader;
 mod compression;
+mod dds;
+mod gdeflate;
 mod file_ext;
 mod msg;
+mod msgdb;
 mod rsz;
 mod tex;
 mod user;
 mod dersz;
 mod pog;
 mod font;
+mod manifest;
+mod mot;
+mod gltf;
+mod rcol;
+mod report;
+mod translation;
 
 extern crate image;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use dersz::{DeRsz, ENUM_FILE, RSZ_FILE};
 use font::Oft;
+use manifest::Manifest;
+use mot::{Mot, MotList};
 use msg::Msg;
 use pog::{Pog, PogList, PogPoint};
+use reerr::Context;
+use report::{ErrorInfo, Report, ReportEntry};
+use rcol::Rcol;
 use rsz::Rsz;
 use serde::Serialize;
 use std::error::Error;
 use std::fs::{self, read_to_string,File};
 use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::time::SystemTime;
-use tex::Tex;
+use std::time::{Instant, SystemTime};
+use tex::{CubemapLayout, HdrFormat, SwizzleRules, Tex, TexExport};
 use user::User;
 
 type Result<T> = std::result::Result<T, Box<dyn Error>>;
@@ -45,6 +60,194 @@ struct Args {
 
     #[arg(short('o'), long, default_value_t = String::from("outputs"))]
     out_dir: String,
+
+    /// Export msg files for translation instead of json (po, xliff, xliff2, csv)
+    #[arg(long)]
+    export: Option<String>,
+
+    /// Merge a po/xliff/csv translation into the msg file and rebuild it
+    #[arg(long)]
+    import: Option<String>,
+
+    /// Language that po/xliff sources come from
+    #[arg(long, default_value_t = String::from("en"))]
+    source_lang: String,
+
+    /// Language to put in po/xliff targets, every other language in the file if not set
+    #[arg(long)]
+    target_lang: Option<String>,
+
+    /// Drop markup tags from msg text in the json
+    #[arg(long)]
+    strip_markup: bool,
+
+    /// Warn about msg translations whose placeholders differ from the source language
+    #[arg(long)]
+    check_placeholders: bool,
+
+    /// Comma separated languages to keep in msg json, all of them if not set
+    #[arg(long, value_delimiter = ',')]
+    langs: Option<Vec<String>>,
+
+    /// Write one msg json per language
+    #[arg(long)]
+    split_langs: bool,
+
+    /// Write an ndjson line per file of a -l run, followed by a summary line
+    #[arg(long)]
+    report: Option<String>,
+
+    /// Skip files of a -l run that haven't changed since they were last dumped to the output directory
+    #[arg(long)]
+    incremental: bool,
+
+    /// Write every mip level of textures, not just the largest
+    #[arg(long)]
+    all_mips: bool,
+
+    /// Write every array slice, cubemap face and 3D depth slice of textures
+    #[arg(long)]
+    all_slices: bool,
+
+    /// How cubemaps are written: faces, cross or equirect
+    #[arg(long, default_value_t = String::from("faces"))]
+    cubemap: String,
+
+    /// Write textures as .dds with the original block compressed data instead of decoding them to .png
+    #[arg(long)]
+    dds: bool,
+
+    /// What HDR (BC6H) textures are written as: exr, hdr, or png clamped to 0-1
+    #[arg(long, default_value_t = String::from("exr"))]
+    hdr_format: String,
+
+    /// Texture channel swizzle, a default like rgba or rgn1 (n rebuilds normal Z) and/or format or
+    /// file name tag rules like BC5=rgn1,_NRM=rgn1, `auto` adds rules for normal maps
+    #[arg(long, default_value_t = String::from("rgba"))]
+    swizzle: String,
+
+    /// Write each texture channel as a separate grayscale .png (.r, .g, .b, .a)
+    #[arg(long)]
+    split_channels: bool,
+
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Combine every .msg file under a directory into one json keyed by guid
+    MsgDb {
+        dir: String,
+
+        #[arg(short('o'), long, default_value_t = String::from("combined_msgs.json"))]
+        output: String,
+
+        /// Comma separated languages to keep, all of them if not set
+        #[arg(long, value_delimiter = ',')]
+        langs: Option<Vec<String>>,
+    },
+    /// Print the header of .tex files (or every .tex under directories) without decoding them
+    Info {
+        #[arg(required = true)]
+        paths: Vec<String>,
+
+        /// Write the headers and tex info tables to a json file instead
+        #[arg(long)]
+        json: Option<String>,
+    },
+    /// Write the RSZ block of .user files (or every .user under directories) back from their parsed values and check it matches the original
+    CheckRsz {
+        #[arg(required = true)]
+        paths: Vec<String>,
+    },
+    /// Time BC1/BC3/BC5/BC7 decoding of synthetic blocks, checking the threaded decoder against the simple one
+    BenchTex {
+        /// Width and height of the synthetic textures
+        #[arg(long, default_value_t = 2048)]
+        size: usize,
+
+        /// Runs per decoder, the fastest is reported
+        #[arg(long, default_value_t = 3)]
+        iterations: usize,
+
+        /// Decoding threads, all cores if not set
+        #[arg(long)]
+        threads: Option<usize>,
+    },
+    /// Make a .tex from images, one per array slice (or six for a cubemap's faces)
+    PackTex {
+        #[arg(required = true)]
+        images: Vec<String>,
+
+        /// Output file, the tex version is taken from its extension (file.tex.241106027)
+        #[arg(short('o'), long)]
+        output: String,
+
+        /// DXGI format name, e.g. BC7_UNORM_SRGB, BC1, BC4, BC5, R8G8B8A8_UNORM
+        #[arg(long, default_value_t = String::from("BC7_UNORM"))]
+        format: String,
+
+        /// Number of mips, 0 for all of them down to 1x1
+        #[arg(long, default_value_t = 0)]
+        mips: usize,
+
+        /// Mip filter: nearest, triangle, catmullrom, gaussian or lanczos3
+        #[arg(long, default_value_t = String::from("triangle"))]
+        filter: String,
+
+        /// Mark the texture as a cubemap, images are the +x -x +y -y +z -z faces
+        #[arg(long)]
+        cubemap: bool,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct DumpOptions {
+    root_dir: Option<String>,
+    export: Option<translation::Format>,
+    import: Option<String>,
+    source_lang: usize,
+    target_lang: Option<usize>,
+    strip_markup: bool,
+    check_placeholders: bool,
+    langs: Option<Vec<usize>>,
+    split_langs: bool,
+    tex: TexExport,
+}
+
+fn parse_langs(langs: &Option<Vec<String>>) -> Result<Option<Vec<usize>>> {
+    Ok(langs.as_ref().map(|langs| {
+        langs.iter()
+            .map(|lang| translation::lang_from_str(lang).ok_or_else(|| format!("Unknown language {lang}")))
+            .collect::<std::result::Result<Vec<_>, _>>()
+    }).transpose()?)
+}
+
+impl DumpOptions {
+    fn from_args(args: &Args) -> Result<DumpOptions> {
+        let lang = |lang: &str| translation::lang_from_str(lang).ok_or_else(|| format!("Unknown language {lang}"));
+        Ok(DumpOptions {
+            root_dir: args.root_dir.clone(),
+            export: args.export.as_deref().map(translation::Format::from_name).transpose()?,
+            import: args.import.clone(),
+            source_lang: lang(&args.source_lang)?,
+            target_lang: args.target_lang.as_deref().map(lang).transpose()?,
+            strip_markup: args.strip_markup,
+            check_placeholders: args.check_placeholders,
+            langs: parse_langs(&args.langs)?,
+            split_langs: args.split_langs,
+            tex: TexExport {
+                all_mips: args.all_mips,
+                all_slices: args.all_slices,
+                cubemap: CubemapLayout::from_name(&args.cubemap)?,
+                dds: args.dds,
+                hdr_format: HdrFormat::from_name(&args.hdr_format)?,
+                swizzle: SwizzleRules::from_spec(&args.swizzle)?,
+                split_channels: args.split_channels,
+            },
+        })
+    }
 }
 
 fn construct_paths(file: String, prefix: Option<String>, out_dir_base: String, preserve_structure: bool) -> Result<(PathBuf, PathBuf)> {
@@ -74,14 +277,44 @@ fn construct_paths(file: String, prefix: Option<String>, out_dir_base: String, p
 
 enum FileType {
     Msg(u32),
+    MsgJson(u32),
     User(u32),
     Tex(u32),
+    Mot(u32),
+    MotList(u32),
+    Rcol(u32),
     Oft,
     Pog,
     PogList,
     Unknown
 }
 
+impl FileType {
+    fn name(&self) -> &'static str {
+        match self {
+            FileType::Msg(_) => "msg",
+            FileType::MsgJson(_) => "msg_json",
+            FileType::User(_) => "user",
+            FileType::Tex(_) => "tex",
+            FileType::Mot(_) => "mot",
+            FileType::MotList(_) => "motlist",
+            FileType::Rcol(_) => "rcol",
+            FileType::Oft => "oft",
+            FileType::Pog => "pog",
+            FileType::PogList => "poglst",
+            FileType::Unknown => "unknown",
+        }
+    }
+
+    fn version(&self) -> Option<u32> {
+        match self {
+            FileType::Msg(v) | FileType::MsgJson(v) | FileType::User(v) | FileType::Tex(v)
+                | FileType::Mot(v) | FileType::MotList(v) | FileType::Rcol(v) => Some(*v),
+            _ => None,
+        }
+    }
+}
+
 fn get_file_ext(file_name: String) -> Result<FileType> {
     let split = file_name.split('.').collect::<Vec<_>>();
     if split.len() < 2 {
@@ -101,10 +334,14 @@ fn get_file_ext(file_name: String) -> Result<FileType> {
         Some(ext) => {
             match *ext {
                 "user" => FileType::User(version),
+                "msg" if split.last() == Some(&"json") => FileType::MsgJson(version),
                 "msg" => FileType::Msg(version),
                 "tex" => FileType::Tex(version),
                 "pog" => FileType::Pog,
                 "poglst" => FileType::PogList,
+                "mot" => FileType::Mot(version),
+                "motlist" => FileType::MotList(version),
+                "rcol" => FileType::Rcol(version),
                 "oft" => FileType::Oft,
                 _ => FileType::Unknown
             }
@@ -117,7 +354,17 @@ fn get_file_ext(file_name: String) -> Result<FileType> {
     Ok(file_type)
 }
 
-fn dump_file(root_dir: Option<String>, file_path: PathBuf, output_path: PathBuf) -> Result<()> {
+fn warn_placeholders(msg: &Msg, source_lang: usize, file_path: &Path) {
+    for mismatch in msg.check_placeholders(source_lang) {
+        eprintln!("[WARNING] {file_path:?} {} ({}): missing {:?}, extra {:?}",
+            mismatch.name, translation::lang_name(mismatch.lang), mismatch.missing, mismatch.extra);
+    }
+}
+
+// returns the files that were written
+fn dump_file(options: &DumpOptions, file_path: PathBuf, output_path: PathBuf) -> Result<Vec<PathBuf>> {
+    let root_dir = options.root_dir.clone();
+    let mut outputs = Vec::new();
     let file_name = match file_path.file_name() {
         Some(file_name) => file_name,
         None => {
@@ -127,15 +374,92 @@ fn dump_file(root_dir: Option<String>, file_path: PathBuf, output_path: PathBuf)
     let file_type = get_file_ext(file_name.to_string_lossy().to_string())?;
     let res = match file_type {
         FileType::Msg(_v) => {
+            let mut msg = Msg::new(file_path.to_string_lossy().to_string())?;
+
+            if let Some(import) = &options.import {
+                let data = read_to_string(import)?;
+                let format = translation::Format::from_path(Path::new(import), &data)?;
+                let translations = translation::import(&data, format, options.target_lang)?;
+                for key in translation::apply(&mut msg, translations) {
+                    eprintln!("[WARNING] {import}: no message for {key}");
+                }
+                warn_placeholders(&msg, options.source_lang, &file_path);
+                let mut data = Vec::new();
+                msg.write(&mut data)?;
+                fs::create_dir_all(output_path.parent().unwrap())?;
+                fs::write(&output_path, data)?;
+                println!("[INFO] Saved File {:?}", &output_path);
+                outputs.push(output_path.clone());
+                return Ok(outputs)
+            }
+
+            if let Some(format) = options.export {
+                let original = file_name.to_string_lossy().to_string();
+                let mut files = Vec::new();
+                if format == translation::Format::Csv {
+                    files.push((output_path.file_name().unwrap().to_string_lossy().to_string() + ".csv",
+                        translation::export(&msg, format, options.source_lang, options.source_lang, &original)));
+                } else {
+                    let targets = match options.target_lang {
+                        Some(target) => vec![target],
+                        None => msg.languages().into_iter().filter(|&lang| lang != options.source_lang).collect(),
+                    };
+                    for target in targets {
+                        let name = format!("{}.{}.{}", output_path.file_name().unwrap().to_string_lossy(),
+                            translation::lang_code(target), format.extension());
+                        files.push((name, translation::export(&msg, format, options.source_lang, target, &original)));
+                    }
+                }
+                fs::create_dir_all(output_path.parent().unwrap())?;
+                for (name, data) in files {
+                    let output_path = output_path.with_file_name(name);
+                    fs::write(&output_path, data)?;
+                    println!("[INFO] Saved File {:?}", &output_path);
+                    outputs.push(output_path.clone());
+                }
+                return Ok(outputs)
+            }
+
+            if options.check_placeholders {
+                warn_placeholders(&msg, options.source_lang, &file_path);
+            }
+
+            if options.split_langs {
+                let langs = msg.languages().into_iter()
+                    .filter(|lang| options.langs.as_ref().is_none_or(|langs| langs.contains(lang)));
+                fs::create_dir_all(output_path.parent().unwrap())?;
+                for lang in langs {
+                    let output_path = output_path.with_file_name(format!("{}.{}.json",
+                        output_path.file_name().unwrap().to_string_lossy(), translation::lang_code(lang)));
+                    let mut f = File::create(&output_path)?;
+                    msg.save(&mut f, options.strip_markup, Some(&[lang]));
+                    println!("[INFO] Saved File {:?}", &output_path);
+                    outputs.push(output_path.clone());
+                }
+                return Ok(outputs)
+            }
+
             let mut output_path = output_path.clone();
             output_path.set_file_name(output_path.file_name().unwrap().to_str().unwrap().to_string() + ".json");
-            let msg = Msg::new(file_path.to_string_lossy().to_string())?;
-
             println!("Trying to save to {:?}", &output_path);
             let _ = fs::create_dir_all(output_path.parent().unwrap())?;
             let mut f = std::fs::File::create(&output_path).expect("Error Creating File");
-            msg.save(&mut f);
+            msg.save(&mut f, options.strip_markup, options.langs.as_deref());
             println!("Saved file");
+            outputs.push(output_path.clone());
+            Ok(())
+        },
+        FileType::MsgJson(v) => {
+            // x.msg.23.json gets rebuilt into x.msg.23
+            let mut output_path = output_path.clone();
+            output_path.set_extension("");
+            let msg = Msg::from_json(File::open(&file_path)?, v)?;
+            let mut data = Vec::new();
+            msg.write(&mut data)?;
+            fs::create_dir_all(output_path.parent().unwrap())?;
+            fs::write(&output_path, data)?;
+            println!("[INFO] Saved File {:?}", &output_path);
+            outputs.push(output_path.clone());
             Ok(())
         },
         FileType::User(_v) => {
@@ -147,12 +471,13 @@ fn dump_file(root_dir: Option<String>, file_path: PathBuf, output_path: PathBuf)
             //output_path.push(file_path.file_name().unwrap().to_str().unwrap().to_string() + ".json");
 
             let json_res = serde_json::to_string_pretty(&nodes); 
-            return match json_res {
+            match json_res {
                 Ok(json) => {
                     let _ = fs::create_dir_all(output_path.parent().unwrap())?;
                     let mut f = std::fs::File::create(&output_path).expect("Error Creating File");
                     f.write_all(json.as_bytes())?;
                     println!("[INFO] Saved File {:?}", &output_path);
+                    outputs.push(output_path.clone());
                     Ok(())
                 },
                 Err(e) => {
@@ -163,19 +488,56 @@ fn dump_file(root_dir: Option<String>, file_path: PathBuf, output_path: PathBuf)
         FileType::Tex(_v) => {
             let file = File::open(file_path.clone())?;
             let tex = Tex::new(file)?;
-            let rgba = tex.to_rgba(0, 0)?;
-            println!("{}", rgba.data.len());
-            let mut output_path = output_path.clone();
-            output_path.set_file_name(output_path.file_name().unwrap().to_str().unwrap().to_string() + ".png");
-            println!("saving to {output_path:?}");
             fs::create_dir_all(output_path.parent().unwrap())?;
-            image::save_buffer(
-                &Path::new(&output_path),
-                &rgba.data,
-                rgba.width,
-                rgba.height,
-                image::ExtendedColorType::Rgba8,
-            )?;
+            if options.tex.dds {
+                let mut output_path = output_path.clone();
+                output_path.set_file_name(output_path.file_name().unwrap().to_str().unwrap().to_string() + ".dds");
+                println!("saving {} to {output_path:?}", tex.tex_format()?.name);
+                dds::write_dds(&tex, std::io::BufWriter::new(File::create(&output_path)?))?;
+                outputs.push(output_path);
+                return Ok(outputs)
+            }
+            if tex.is_hdr() && options.tex.hdr_format != HdrFormat::Png {
+                let ext = options.tex.hdr_format.extension();
+                for (suffix, rgba) in tex.export_images(&options.tex, |t, m, s| tex.to_rgba_f32(t, m, s))? {
+                    let mut output_path = output_path.clone();
+                    output_path.set_file_name(output_path.file_name().unwrap().to_str().unwrap().to_string() + &suffix + "." + ext);
+                    println!("saving to {output_path:?}");
+                    match options.tex.hdr_format {
+                        // radiance hdr has no alpha
+                        HdrFormat::Hdr => image::Rgb32FImage::from_raw(rgba.width, rgba.height,
+                            rgba.data.chunks(4).flat_map(|p| [p[0], p[1], p[2]]).collect()).unwrap().save(&output_path)?,
+                        _ => image::Rgba32FImage::from_raw(rgba.width, rgba.height, rgba.data).unwrap().save(&output_path)?,
+                    }
+                    outputs.push(output_path);
+                }
+                return Ok(outputs)
+            }
+            let swizzle = options.tex.swizzle.for_texture(&file_name.to_string_lossy(), tex.tex_format()?);
+            for (suffix, rgba) in tex.export_images(&options.tex, |t, m, s| tex.to_rgba(t, m, s, swizzle))? {
+                let base_name = output_path.file_name().unwrap().to_str().unwrap().to_string() + &suffix;
+                if options.tex.split_channels {
+                    for (channel, data) in tex::split_channels(&rgba, swizzle) {
+                        let mut output_path = output_path.clone();
+                        output_path.set_file_name(format!("{base_name}.{channel}.png"));
+                        println!("saving to {output_path:?}");
+                        image::save_buffer(&output_path, &data, rgba.width, rgba.height, image::ExtendedColorType::L8)?;
+                        outputs.push(output_path);
+                    }
+                    continue;
+                }
+                let mut output_path = output_path.clone();
+                output_path.set_file_name(base_name + ".png");
+                println!("saving to {output_path:?}");
+                image::save_buffer(
+                    &Path::new(&output_path),
+                    &rgba.data,
+                    rgba.width,
+                    rgba.height,
+                    image::ExtendedColorType::Rgba8,
+                )?;
+                outputs.push(output_path);
+            }
             Ok(())
         },
         FileType::Pog => {
@@ -194,12 +556,13 @@ fn dump_file(root_dir: Option<String>, file_path: PathBuf, output_path: PathBuf)
                 points: pog.points,
                 nodes,
             }); 
-            return match json_res {
+            match json_res {
                 Ok(json) => {
                     let _ = fs::create_dir_all(output_path.parent().unwrap())?;
                     let mut f = std::fs::File::create(&output_path).expect("Error Creating File");
                     f.write_all(json.as_bytes())?;
                     println!("[INFO] Saved File {:?}", &output_path);
+                    outputs.push(output_path.clone());
                     Ok(())
                 },
                 Err(e) => {
@@ -213,12 +576,13 @@ fn dump_file(root_dir: Option<String>, file_path: PathBuf, output_path: PathBuf)
             let mut output_path = output_path.clone();
             output_path.set_file_name(output_path.file_name().unwrap().to_str().unwrap().to_string() + ".json");
             let json_res = serde_json::to_string_pretty(&poglst);
-            return match json_res {
+            match json_res {
                 Ok(json) => {
                     let _ = fs::create_dir_all(output_path.parent().unwrap())?;
                     let mut f = std::fs::File::create(&output_path).expect("Error Creating File");
                     f.write_all(json.as_bytes())?;
                     println!("[INFO] Saved File {:?}", &output_path);
+                    outputs.push(output_path.clone());
                     Ok(())
                 },
                 Err(e) => {
@@ -235,14 +599,84 @@ fn dump_file(root_dir: Option<String>, file_path: PathBuf, output_path: PathBuf)
             let mut f = std::fs::File::create(&output_path).expect("Error Creating File");
             f.write(&oft.data)?;
             println!("[INFO] Saved File {:?}", &output_path);
+            outputs.push(output_path.clone());
             Ok(())
         }
+        FileType::Mot(_v) => {
+            let file = File::open(file_path.clone())?;
+            let mot = Mot::new(file)?;
+            if mot.bones.is_empty() {
+                return Err(format!("{file_name:?} has no skeleton to animate").into());
+            }
+            let mut output_path = output_path.clone();
+            output_path.set_file_name(output_path.file_name().unwrap().to_str().unwrap().to_string() + ".gltf");
+            fs::create_dir_all(output_path.parent().unwrap())?;
+            gltf::save_animations(&mot.bones, &[&mot], &output_path)?;
+            outputs.push(output_path.with_extension("bin"));
+            println!("[INFO] Saved File {:?}", &output_path);
+            outputs.push(output_path.clone());
+            Ok(())
+        },
+        FileType::MotList(_v) => {
+            let file = File::open(file_path.clone())?;
+            let motlist = MotList::new(file)?;
+            let skeleton = match motlist.skeleton() {
+                Some(skeleton) => skeleton,
+                None => return Err(format!("{file_name:?} has no skeleton to animate").into()),
+            };
+            let mut output_path = output_path.clone();
+            output_path.set_file_name(output_path.file_name().unwrap().to_str().unwrap().to_string() + ".gltf");
+            fs::create_dir_all(output_path.parent().unwrap())?;
+            let mots = motlist.mots.iter().collect::<Vec<_>>();
+            gltf::save_animations(skeleton, &mots, &output_path)?;
+            outputs.push(output_path.with_extension("bin"));
+            println!("[INFO] Saved File {:?}", &output_path);
+            outputs.push(output_path.clone());
+            Ok(())
+        },
+        FileType::Rcol(v) => {
+            let file = File::open(file_path.clone())?;
+            let rcol = Rcol::new(file, v)?.deserialize(root_dir)?;
+            let mut output_path = output_path.clone();
+            output_path.set_file_name(output_path.file_name().unwrap().to_str().unwrap().to_string() + ".json");
+            let json_res = serde_json::to_string_pretty(&rcol);
+            match json_res {
+                Ok(json) => {
+                    fs::create_dir_all(output_path.parent().unwrap())?;
+                    let mut f = std::fs::File::create(&output_path).expect("Error Creating File");
+                    f.write_all(json.as_bytes())?;
+                    println!("[INFO] Saved File {:?}", &output_path);
+                    outputs.push(output_path.clone());
+                    Ok(())
+                },
+                Err(e) => {
+                    Err(format!("File: {file_path:?}\nReason: {e}").into())
+                }
+            }
+        },
         FileType::Unknown => return Err(format!("Unknown File Type {file_name:?}").into()),
     };
-    res
+    res.map(|()| outputs)
+}
+
+// Files given directly are kept as is, directories are searched for files of that type
+fn collect_files(paths: &[String], file_type: &str) -> Vec<PathBuf> {
+    let is_type = |path: &Path| path.file_name().and_then(|name| name.to_str()).is_some_and(|name| name.split('.').nth(1) == Some(file_type));
+    let mut files = vec![];
+    for path in paths {
+        let path = PathBuf::from(path);
+        if path.is_dir() {
+            let mut found = find_files_with_extension(path, "");
+            found.retain(|path| is_type(path));
+            found.sort();
+            files.extend(found);
+        } else {
+            files.push(path);
+        }
+    }
+    files
 }
 
-#[allow(dead_code)]
 fn find_files_with_extension(base_dir: PathBuf, extension: &str) -> Vec<PathBuf> {
     let mut results = Vec::new();
     let mut paths: Vec<PathBuf> = Vec::new();
@@ -266,9 +700,22 @@ fn find_files_with_extension(base_dir: PathBuf, extension: &str) -> Vec<PathBuf>
     results
 }
 
-fn dump_all(root_dir: Option<String>, out_dir: String, list_file: String) -> Result<()> {
+fn dump_all(options: &DumpOptions, out_dir: String, list_file: String, report_file: Option<String>, incremental: bool) -> Result<()> {
+    let root_dir = options.root_dir.clone();
     let list = read_to_string(&list_file).expect("Could not open list file");
     let list: Vec<&str> = list.lines().collect();
+    let mut report = Report::new(report_file.as_deref().map(Path::new))?;
+    let mut manifest = if incremental {
+        let hash = |file: Option<&String>| format!("{:016x}", file.map(|f| manifest::hash_file(Path::new(f))).unwrap_or(0));
+        Some(Manifest::load(
+            Path::new(&out_dir),
+            hash(RSZ_FILE.get()),
+            hash(ENUM_FILE.get()),
+            format!("{:016x}", manifest::fnv1a(format!("{options:?}").as_bytes())),
+        ))
+    } else {
+        None
+    };
     for file in list {
         let paths = construct_paths(file.to_string(), root_dir.clone(), out_dir.clone(), true);
         let (file_path, output_path) = match paths {
@@ -279,14 +726,56 @@ fn dump_all(root_dir: Option<String>, out_dir: String, list_file: String) -> Res
             }
         };
         eprintln!("Dumping File: {file_path:?}");
-        match dump_file(root_dir.clone(), file_path.clone(), output_path.clone()) {
-            Ok(()) => (),
+        let file_type = file_path.file_name()
+            .and_then(|name| get_file_ext(name.to_string_lossy().to_string()).ok())
+            .unwrap_or(FileType::Unknown);
+        let start = Instant::now();
+        if let Some(outputs) = manifest.as_mut().and_then(|manifest| manifest.unchanged(&file_path)) {
+            report.record(ReportEntry {
+                input_bytes: fs::metadata(&file_path).map(|m| m.len()).unwrap_or(0),
+                output_bytes: 0,
+                input: file_path,
+                file_type: file_type.name(),
+                version: file_type.version(),
+                outputs,
+                duration_ms: 0.0,
+                skipped: true,
+                error: None,
+            })?;
+            continue
+        }
+        let res = dump_file(options, file_path.clone(), output_path.clone()).file_context(&file_path.to_string_lossy());
+        let duration_ms = start.elapsed().as_secs_f64() * 1000.0;
+        let (outputs, error) = match res {
+            Ok(outputs) => (outputs, None),
             Err(e) => {
                 eprintln!("[ERROR] Error dumping file {e} \n\t{:?}\n\t{:?}", file_path, output_path);
-                continue
+                (Vec::new(), Some(ErrorInfo::new(e.as_ref())))
             }
         };
+        if let Some(manifest) = &mut manifest {
+            match error {
+                None => manifest.update(&file_path, outputs.clone()),
+                Some(_) => manifest.remove(&file_path),
+            }
+        }
+        let file_len = |path: &PathBuf| fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        report.record(ReportEntry {
+            input_bytes: file_len(&file_path),
+            output_bytes: outputs.iter().map(file_len).sum(),
+            input: file_path,
+            file_type: file_type.name(),
+            version: file_type.version(),
+            outputs,
+            duration_ms,
+            skipped: false,
+            error,
+        })?;
     }
+    if let Some(manifest) = &manifest {
+        manifest.save(Path::new(&out_dir))?;
+    }
+    report.finish()?;
     Ok(())
 }
 
@@ -315,15 +804,103 @@ fn main() -> Result<()> {
     let now = SystemTime::now();
     let args = Args::parse();
     println!("{:#?}", args);
+    let options = DumpOptions::from_args(&args)?;
+
+    if let Some(Commands::MsgDb { dir, output, langs }) = &args.command {
+        let langs = parse_langs(langs)?;
+        let files = find_files_with_extension(PathBuf::from(dir), "");
+        let db = msgdb::build(files, Path::new(dir), langs)?;
+        for conflict in &db.conflicts {
+            eprintln!("[WARNING] Conflicting {:?} {}: kept {}, dropped {}", conflict.kind, conflict.key, conflict.kept, conflict.dropped);
+        }
+        let mut f = File::create(output)?;
+        f.write_all(serde_json::to_string_pretty(&db)?.as_bytes())?;
+        println!("[INFO] Saved {} messages with {} conflicts to {:?}", db.msgs.len(), db.conflicts.len(), output);
+        return Ok(())
+    }
+
+    if let Some(Commands::Info { paths, json }) = &args.command {
+        #[derive(Serialize)]
+        struct InfoEntry {
+            file: String,
+            header: Option<tex::TexHeader>,
+            error: Option<ErrorInfo>,
+        }
+        let mut entries = vec![];
+        for path in collect_files(paths, "tex") {
+            let header = File::open(&path).map_err(|e| e.into()).and_then(|file| tex::TexHeader::read(&mut std::io::BufReader::new(file)));
+            match &header {
+                Ok(header) if json.is_none() => println!("{}: {header}", path.display()),
+                Err(e) => eprintln!("[ERROR] {}: {e}", path.display()),
+                _ => (),
+            }
+            entries.push(InfoEntry {
+                file: path.to_string_lossy().replace('\\', "/"),
+                error: header.as_ref().err().map(|e| ErrorInfo::new(e.as_ref())),
+                header: header.ok(),
+            });
+        }
+        if let Some(json) = json {
+            let mut f = File::create(json)?;
+            f.write_all(serde_json::to_string_pretty(&entries)?.as_bytes())?;
+            println!("[INFO] Saved {} tex headers to {json:?}", entries.len());
+        }
+        return Ok(())
+    }
+
+    if let Some(Commands::CheckRsz { paths }) = &args.command {
+        let files = collect_files(paths, "user");
+        let mut failed = 0;
+        for path in &files {
+            let result = File::open(path).map_err(|e| e.into())
+                .and_then(|file| User::new(std::io::BufReader::new(file)))
+                .and_then(|user| user.rsz.check_rebuild());
+            match result {
+                Ok(()) => println!("[INFO] {}: ok", path.display()),
+                Err(e) => {
+                    eprintln!("[ERROR] {}: {e}", path.display());
+                    failed += 1;
+                }
+            }
+        }
+        println!("[INFO] {} of {} RSZ blocks rebuilt identically", files.len() - failed, files.len());
+        return Ok(())
+    }
+
+    if let Some(Commands::BenchTex { size, iterations, threads }) = &args.command {
+        bench::bench_decoders(*size, *iterations, threads.unwrap_or_else(compression::decode_threads))?;
+        return Ok(())
+    }
+
+    if let Some(Commands::PackTex { images, output, format, mips, filter, cubemap }) = &args.command {
+        let version = Path::new(output).extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(|ext| ext.parse::<u32>().ok())
+            .ok_or_else(|| format!("Output {output} needs the tex version as its extension, like .tex.241106027"))?;
+        let format = compression::TexFormat::from_name(format).ok_or_else(|| format!("Unknown format {format}"))?;
+        let filter = tex::filter_from_name(filter)?;
+        let images = images.iter().map(|path| {
+            let image = image::open(path)?.to_rgba8();
+            let (width, height) = image.dimensions();
+            Ok(tex::generate_mips(tex::RGBAImage { data: image.into_raw(), width, height }, *mips, filter))
+        }).collect::<Result<Vec<_>>>()?;
+        let mut tex = Tex::from_images(version, format, &images)?;
+        if *cubemap {
+            tex.set_cubemap()?;
+        }
+        tex.write(std::io::BufWriter::new(File::create(output)?))?;
+        println!("[INFO] Saved {} {}x{} with {} mips to {output:?}", format.name, images[0][0].width, images[0][0].height, images[0].len());
+        return Ok(())
+    }
 
     match args.list {
         Some(list) => {
-            dump_all(args.root_dir, args.out_dir, list)?;
+            dump_all(&options, args.out_dir, list, args.report, args.incremental)?;
         }, 
         None => match args.file_name {
             Some(file_name) => {
                 let (file_path, output_path) = construct_paths(file_name.clone(), args.root_dir.clone(), args.out_dir.clone(), false)?;
-                dump_file(args.root_dir, file_path, output_path)?;
+                dump_file(&options, file_path.clone(), output_path).file_context(&file_path.to_string_lossy())?;
             },
             None => println!("Must provide file name"),
         }
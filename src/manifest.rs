@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use serde::{Deserialize, Serialize};
+
+use crate::reerr::Result;
+
+pub const MANIFEST_NAME: &str = ".mhwsgen-manifest.json";
+
+// FNV-1a, good enough to tell if a file changed and needs no extra crates
+pub fn fnv1a(data: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in data {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+pub fn hash_file(path: &Path) -> u64 {
+    fs::read(path).map(|data| fnv1a(&data)).unwrap_or(0)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputInfo {
+    pub size: u64,
+    pub mtime: u64,
+    pub hash: String,
+    pub outputs: Vec<PathBuf>,
+}
+
+// Everything that was dumped into an output directory and what it was dumped with.
+// If the rsz dump, enum file or options change, every entry is stale.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    pub rsz_hash: String,
+    pub enum_hash: String,
+    pub options_hash: String,
+    pub files: HashMap<String, InputInfo>,
+}
+
+fn stat(path: &Path) -> Option<(u64, u64)> {
+    let meta = fs::metadata(path).ok()?;
+    let mtime = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_secs();
+    Some((meta.len(), mtime))
+}
+
+impl Manifest {
+    pub fn load(out_dir: &Path, rsz_hash: String, enum_hash: String, options_hash: String) -> Manifest {
+        let old: Option<Manifest> = fs::read_to_string(out_dir.join(MANIFEST_NAME)).ok()
+            .and_then(|data| serde_json::from_str(&data).ok());
+        match old {
+            Some(old) if old.rsz_hash == rsz_hash && old.enum_hash == enum_hash && old.options_hash == options_hash => old,
+            Some(_) => {
+                eprintln!("[INFO] Type definitions or options changed since the last run, dumping everything");
+                Manifest { rsz_hash, enum_hash, options_hash, files: HashMap::new() }
+            },
+            None => Manifest { rsz_hash, enum_hash, options_hash, files: HashMap::new() },
+        }
+    }
+
+    // Some(outputs) if the input hasn't changed since it was last dumped. Size and mtime are
+    // checked first so unchanged files don't need to be read.
+    pub fn unchanged(&mut self, input: &Path) -> Option<Vec<PathBuf>> {
+        let key = input.to_string_lossy().to_string();
+        let info = self.files.get_mut(&key)?;
+        if !info.outputs.iter().all(|output| output.exists()) {
+            return None
+        }
+        let (size, mtime) = stat(input)?;
+        if size != info.size {
+            return None
+        }
+        if mtime != info.mtime {
+            if format!("{:016x}", hash_file(input)) != info.hash {
+                return None
+            }
+            // touched but not changed
+            info.mtime = mtime;
+        }
+        Some(info.outputs.clone())
+    }
+
+    pub fn update(&mut self, input: &Path, outputs: Vec<PathBuf>) {
+        let key = input.to_string_lossy().to_string();
+        match stat(input) {
+            Some((size, mtime)) => {
+                let hash = format!("{:016x}", hash_file(input));
+                self.files.insert(key, InputInfo { size, mtime, hash, outputs });
+            },
+            None => {
+                self.files.remove(&key);
+            },
+        }
+    }
+
+    pub fn remove(&mut self, input: &Path) {
+        self.files.remove(&input.to_string_lossy().to_string());
+    }
+
+    pub fn save(&self, out_dir: &Path) -> Result<()> {
+        fs::create_dir_all(out_dir)?;
+        fs::write(out_dir.join(MANIFEST_NAME), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
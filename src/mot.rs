@@ -0,0 +1,588 @@
+use crate::file_ext::*;
+use crate::reerr::{Result, FileParseError::*};
+use std::io::{Read, Seek, SeekFrom};
+
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct MotBone {
+    pub name: String,
+    pub parent: Option<usize>,
+    pub translation: [f32; 3],
+    pub rotation: [f32; 4],
+    pub index: u32,
+    pub hash: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrackKind {
+    Translation,
+    Rotation,
+    Scale,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct MotTrack {
+    pub kind: TrackKind,
+    pub compression: u32,
+    pub frames: Vec<f32>,
+    // xyz for translation/scale, xyzw for rotation
+    pub values: Vec<[f32; 4]>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MotBoneClip {
+    pub bone_index: u16,
+    pub bone_hash: u32,
+    pub tracks: Vec<MotTrack>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MotEventKey {
+    pub frame: f32,
+    pub interpolation: u32,
+    // raw 8 byte key value, its meaning depends on the property type
+    pub value: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct MotEventProperty {
+    pub name: String,
+    pub property_type: u8,
+    pub start_frame: f32,
+    pub end_frame: f32,
+    pub keys: Vec<MotEventKey>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MotEventTrack {
+    pub name: String,
+    pub properties: Vec<MotEventProperty>,
+}
+
+// An embedded CLIP block, the event timeline of a mot (sounds, effects, flags...)
+#[derive(Debug, Clone)]
+pub struct MotEventClip {
+    pub version: u32,
+    pub frame_count: f32,
+    pub tracks: Vec<MotEventTrack>,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct Mot {
+    pub version: u32,
+    pub name: String,
+    pub frame_count: f32,
+    pub blending: f32,
+    pub frame_rate: u16,
+    pub bones: Vec<MotBone>,
+    pub clips: Vec<MotBoneClip>,
+    pub events: Vec<MotEventClip>,
+}
+
+const TRACK_TRANSLATION: u16 = 1;
+const TRACK_ROTATION: u16 = 2;
+const TRACK_SCALE: u16 = 4;
+
+const BONE_HEADER_SIZE: u64 = 0x50;
+
+const CLIP_TRACK_SIZE: u64 = 0x20;
+// property types up to this one hold child properties instead of keys
+const CLIP_PROPERTY_CONTAINER: u8 = 1;
+
+// Reads `bits` wide unsigned values packed from the lowest bit up, normalized to 0..1
+fn unpack_bits(raw: u64, bits: u32, count: usize) -> [f32; 4] {
+    let mask = (1u64 << bits) - 1;
+    let mut out = [0.0; 4];
+    for (i, o) in out.iter_mut().take(count).enumerate() {
+        *o = ((raw >> (bits * i as u32)) & mask) as f32 / mask as f32;
+    }
+    out
+}
+
+fn read_packed<F: Read>(file: &mut F, bytes: usize) -> Result<u64> {
+    let mut buf = [0u8; 8];
+    file.read_exact(&mut buf[..bytes])?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn quat_from_xyz(x: f32, y: f32, z: f32) -> [f32; 4] {
+    let w = (1.0 - x * x - y * y - z * z).max(0.0).sqrt();
+    [x, y, z, w]
+}
+
+impl MotTrack {
+    fn read_frame_index<F: Read>(file: &mut F, index_type: u32) -> Result<f32> {
+        Ok(match index_type {
+            2 => file.read_u8()? as f32,
+            4 => file.read_u16()? as f32,
+            5 => file.read_u32()? as f32,
+            x => return Err(Box::new(Unsupported { source: format!("Unknown mot frame index type {x}") })),
+        })
+    }
+
+    fn read_vector<F: Read>(file: &mut F, compression: u32, unpack: &[f32; 8]) -> Result<[f32; 4]> {
+        let (scale, offset) = (&unpack[0..4], &unpack[4..8]);
+        let lerp = |n: [f32; 4]| [
+            scale[0] * n[0] + offset[0],
+            scale[1] * n[1] + offset[1],
+            scale[2] * n[2] + offset[2],
+            0.0,
+        ];
+        Ok(match compression {
+            0x00000 => [file.read_f32()?, file.read_f32()?, file.read_f32()?, 0.0],
+            0x20000 => lerp(unpack_bits(file.read_u16()? as u64, 5, 3)),
+            0x40000 => lerp(unpack_bits(file.read_u32()? as u64, 10, 3)),
+            0x70000 => lerp(unpack_bits(file.read_u64()?, 21, 3)),
+            // single animated axis, the other two are constant and stored in the unpack data
+            0x31000 => [file.read_f32()?, unpack[1], unpack[2], 0.0],
+            0x32000 => [unpack[0], file.read_f32()?, unpack[2], 0.0],
+            0x33000 => [unpack[0], unpack[1], file.read_f32()?, 0.0],
+            0x34000 => {
+                let v = file.read_f32()?;
+                [v, v, v, 0.0]
+            },
+            0x41000 | 0x42000 | 0x43000 | 0x44000 => {
+                let v = scale[0] * (file.read_u16()? as f32 / 65535.0) + scale[1];
+                match compression {
+                    0x41000 => [v, unpack[2], unpack[3], 0.0],
+                    0x42000 => [unpack[2], v, unpack[3], 0.0],
+                    0x43000 => [unpack[2], unpack[3], v, 0.0],
+                    _ => [v, v, v, 0.0],
+                }
+            },
+            x => return Err(Box::new(Unsupported { source: format!("Unsupported mot vector compression {x:#07x}") })),
+        })
+    }
+
+    fn read_rotation<F: Read>(file: &mut F, compression: u32, unpack: &[f32; 8]) -> Result<[f32; 4]> {
+        let (scale, offset) = (&unpack[0..4], &unpack[4..8]);
+        let lerp = |n: [f32; 4]| quat_from_xyz(
+            scale[0] * n[0] + offset[0],
+            scale[1] * n[1] + offset[1],
+            scale[2] * n[2] + offset[2],
+        );
+        Ok(match compression {
+            0x00000 => quat_from_xyz(file.read_f32()?, file.read_f32()?, file.read_f32()?),
+            0x20000 => lerp(unpack_bits(file.read_u16()? as u64, 5, 3)),
+            0x30000 => lerp(unpack_bits(read_packed(file, 3)?, 8, 3)),
+            0x40000 => lerp(unpack_bits(file.read_u32()? as u64, 10, 3)),
+            0x50000 => lerp(unpack_bits(read_packed(file, 5)?, 13, 3)),
+            0x60000 => lerp(unpack_bits(read_packed(file, 6)?, 16, 3)),
+            0x70000 => lerp(unpack_bits(read_packed(file, 7)?, 18, 3)),
+            0x80000 => lerp(unpack_bits(file.read_u64()?, 21, 3)),
+            0x21000 | 0x22000 | 0x23000 => {
+                let v = scale[0] * (file.read_u16()? as f32 / 65535.0) + scale[1];
+                match compression {
+                    0x21000 => quat_from_xyz(v, 0.0, 0.0),
+                    0x22000 => quat_from_xyz(0.0, v, 0.0),
+                    _ => quat_from_xyz(0.0, 0.0, v),
+                }
+            },
+            0x31000 => quat_from_xyz(file.read_f32()?, 0.0, 0.0),
+            0x32000 => quat_from_xyz(0.0, file.read_f32()?, 0.0),
+            0x33000 => quat_from_xyz(0.0, 0.0, file.read_f32()?),
+            x => return Err(Box::new(Unsupported { source: format!("Unsupported mot rotation compression {x:#07x}") })),
+        })
+    }
+
+    fn new<F: Read + Seek>(file: &mut F, base: u64, version: u32, kind: TrackKind) -> Result<MotTrack> {
+        let flags = file.read_u32()?;
+        let key_count = file.read_u32()?;
+        let (frame_index_offset, frame_data_offset, unpack_data_offset) = if version >= 456 {
+            (file.read_u32()? as u64, file.read_u32()? as u64, file.read_u32()? as u64)
+        } else {
+            let _frame_rate = file.read_u32()?;
+            let _max_frame = file.read_f32()?;
+            (file.read_u64()?, file.read_u64()?, file.read_u64()?)
+        };
+        let next_track = file.tell()?;
+
+        let compression = flags & 0xFF000;
+        let index_type = flags >> 20;
+
+        let frames = if frame_index_offset != 0 {
+            file.seek(SeekFrom::Start(base + frame_index_offset))?;
+            (0..key_count).map(|_| Self::read_frame_index(file, index_type)).collect::<Result<Vec<_>>>()?
+        } else {
+            (0..key_count).map(|i| i as f32).collect()
+        };
+
+        let mut unpack = [1.0, 1.0, 1.0, 1.0, 0.0, 0.0, 0.0, 0.0];
+        if unpack_data_offset != 0 {
+            file.seek(SeekFrom::Start(base + unpack_data_offset))?;
+            for v in unpack.iter_mut() {
+                *v = file.read_f32()?;
+            }
+        }
+
+        file.seek(SeekFrom::Start(base + frame_data_offset))?;
+        let values = (0..key_count).map(|_| match kind {
+            TrackKind::Rotation => Self::read_rotation(file, compression, &unpack),
+            TrackKind::Translation | TrackKind::Scale => Self::read_vector(file, compression, &unpack),
+        }).collect::<Result<Vec<_>>>()?;
+
+        file.seek(SeekFrom::Start(next_track))?;
+        Ok(MotTrack {
+            kind,
+            compression,
+            frames,
+            values,
+        })
+    }
+}
+
+impl MotEventClip {
+    // CLIP header: magic, version, frame count, track/property/key counts, then the offsets of the
+    // track, property and key tables and of the ascii and utf16 name tables. Offsets are relative
+    // to the start of the CLIP block.
+    //   track:    u32 type, u32 property count, u64 first property, u64 ascii name offset, u64 hash
+    //   property: f32 start, f32 end, u32 utf16 hash, u32 ascii hash, u64 utf16 name offset,
+    //             u64 data offset, u64 child start, u16 child count, u8 array index, u8 pad,
+    //             u16 speed points, u8 type, u8 pad
+    //   key:      f32 frame, f32 rate, u32 interpolation, u32 pad, u64 value, u64 pad
+    pub fn new<F: Read + Seek>(file: &mut F, base: u64) -> Result<MotEventClip> {
+        file.seek(SeekFrom::Start(base))?;
+        let magic = file.read_magic()?;
+        if &magic != b"CLIP" {
+            return Err(Box::new(MagicError {
+                real_magic: String::from("CLIP"),
+                read_magic: String::from_utf8_lossy(&magic).to_string()
+            }))
+        }
+        let version = file.read_u32()?;
+        let frame_count = file.read_f32()?;
+        let track_count = file.read_u32()? as u64;
+        let property_count = file.read_u32()? as u64;
+        let key_count = file.read_u32()? as u64;
+        let tracks_offset = file.read_u64()?;
+        let properties_offset = file.read_u64()?;
+        let keys_offset = file.read_u64()?;
+        let names_offset = file.read_u64()?;
+        let unicode_names_offset = file.read_u64()?;
+
+        file.seek(SeekFrom::Start(base + keys_offset))?;
+        let keys = (0..key_count).map(|_| {
+            let frame = file.read_f32()?;
+            let _rate = file.read_f32()?;
+            let interpolation = file.read_u32()?;
+            let _ = file.read_u32()?;
+            let value = file.read_u64()?;
+            let _ = file.read_u64()?;
+            Ok(MotEventKey { frame, interpolation, value })
+        }).collect::<Result<Vec<_>>>()?;
+
+        // (property, child start, child count), children are keys or, for containers, properties
+        file.seek(SeekFrom::Start(base + properties_offset))?;
+        let raw_properties = (0..property_count).map(|_| {
+            let start_frame = file.read_f32()?;
+            let end_frame = file.read_f32()?;
+            let _utf16_hash = file.read_u32()?;
+            let _ascii_hash = file.read_u32()?;
+            let name_offset = file.read_u64()?;
+            let _data_offset = file.read_u64()?;
+            let child_start = file.read_u64()? as usize;
+            let child_count = file.read_u16()? as usize;
+            let _array_index = file.read_u8()?;
+            let _ = file.read_u8()?;
+            let _speed_points = file.read_u16()?;
+            let property_type = file.read_u8()?;
+            let _ = file.read_u8()?;
+            Ok((name_offset, start_frame, end_frame, property_type, child_start, child_count))
+        }).collect::<Result<Vec<_>>>()?;
+        let children = |start: usize, count: usize, len: usize, what: &str| -> Result<std::ops::Range<usize>> {
+            match start.checked_add(count) {
+                Some(end) if end <= len => Ok(start..end),
+                _ => Err(format!("Clip {what} {start}+{count} out of bounds of {len}").into()),
+            }
+        };
+
+        let mut tracks = Vec::new();
+        for i in 0..track_count {
+            file.seek(SeekFrom::Start(base + tracks_offset + i * CLIP_TRACK_SIZE))?;
+            let _track_type = file.read_u32()?;
+            let count = file.read_u32()? as usize;
+            let first = file.read_u64()? as usize;
+            let name_offset = file.read_u64()?;
+            file.seek(SeekFrom::Start(base + names_offset + name_offset))?;
+            let name = file.read_u8str()?;
+
+            // containers are flattened, only the properties holding keys are kept
+            let mut properties = Vec::new();
+            let mut visited = vec![false; raw_properties.len()];
+            let mut pending: Vec<usize> = children(first, count, raw_properties.len(), "properties")?.rev().collect();
+            while let Some(p) = pending.pop() {
+                if std::mem::replace(&mut visited[p], true) {
+                    return Err(format!("Clip property {p} is reached twice").into())
+                }
+                let (name_offset, start_frame, end_frame, property_type, child_start, child_count) = raw_properties[p];
+                if property_type <= CLIP_PROPERTY_CONTAINER {
+                    pending.extend(children(child_start, child_count, raw_properties.len(), "properties")?.rev());
+                    continue
+                }
+                file.seek(SeekFrom::Start(base + unicode_names_offset + name_offset))?;
+                let name = file.read_u16str()?;
+                let keys = keys[children(child_start, child_count, keys.len(), "keys")?].to_vec();
+                properties.push(MotEventProperty { name, property_type, start_frame, end_frame, keys });
+            }
+            tracks.push(MotEventTrack { name, properties });
+        }
+        Ok(MotEventClip { version, frame_count, tracks })
+    }
+}
+
+impl Mot {
+    pub fn new<F: Read + Seek>(mut file: F) -> Result<Mot> {
+        Self::new_at(&mut file, 0)
+    }
+
+    // Offsets inside a mot are relative to its own start, which is not 0 when embedded in a motlist
+    pub fn new_at<F: Read + Seek>(file: &mut F, base: u64) -> Result<Mot> {
+        file.seek(SeekFrom::Start(base))?;
+        let version = file.read_u32()?;
+        let magic = file.read_magic()?;
+        let ext = core::str::from_utf8(&magic)?;
+        if ext != "mot " {
+            return Err(Box::new(MagicError {
+                real_magic: String::from("mot "),
+                read_magic: ext.to_string()
+            }))
+        }
+        let _unkn = file.read_u32()?;
+        let _mot_size = file.read_u32()?;
+        let bones_offset = file.read_u64()?;
+        let bone_clips_offset = file.read_u64()?;
+        let _ = file.read_u64()?;
+        let _ = file.read_u64()?;
+        let clip_offset = file.read_u64()?;
+        let _jmap_offset = file.read_u64()?;
+        let _ex_data_offset = file.read_u64()?;
+        if version >= 456 {
+            let _ = file.read_u64()?;
+        }
+        let names_offset = file.read_u64()?;
+        let frame_count = file.read_f32()?;
+        let blending = file.read_f32()?;
+        let _ = file.read_f32()?;
+        let _ = file.read_f32()?;
+        let bone_count = file.read_u16()?;
+        let bone_clip_count = file.read_u16()?;
+        let clip_count = file.read_u8()?;
+        let _ = file.read_u8()?;
+        let frame_rate = file.read_u16()?;
+
+        file.seek(SeekFrom::Start(base + names_offset))?;
+        let name = file.read_u16str()?;
+
+        // Only the first mot of a motlist usually carries the skeleton
+        let mut bones = Vec::new();
+        if bones_offset != 0 && bone_count != 0 {
+            file.seek(SeekFrom::Start(base + bones_offset))?;
+            let headers_offset = file.read_u64()?;
+            let header_count = file.read_u64()?;
+            let infos = (0..header_count).map(|i| {
+                file.seek(SeekFrom::Start(base + headers_offset + i * BONE_HEADER_SIZE))?;
+                let name_offset = file.read_u64()?;
+                let parent_offset = file.read_u64()?;
+                let _child_offset = file.read_u64()?;
+                let _sibling_offset = file.read_u64()?;
+                let t = file.read_f32vec4()?;
+                let r = file.read_f32vec4()?;
+                let index = file.read_u32()?;
+                let hash = file.read_u32()?;
+                Ok((name_offset, parent_offset, [t.x, t.y, t.z], [r.x, r.y, r.z, r.w], index, hash))
+            }).collect::<Result<Vec<_>>>()?;
+
+            bones = infos.into_iter().map(|(name_offset, parent_offset, translation, rotation, index, hash)| {
+                file.seek(SeekFrom::Start(base + name_offset))?;
+                let name = file.read_u16str()?;
+                let parent = if parent_offset != 0 {
+                    let Some(relative) = parent_offset.checked_sub(headers_offset) else {
+                        return Err(format!("Bone {name} has parent offset {parent_offset:#x} before the bone headers at {headers_offset:#x}").into())
+                    };
+                    Some((relative / BONE_HEADER_SIZE) as usize)
+                } else {
+                    None
+                };
+                Ok(MotBone { name, parent, translation, rotation, index, hash })
+            }).collect::<Result<Vec<_>>>()?;
+        }
+
+        file.seek(SeekFrom::Start(base + bone_clips_offset))?;
+        let clip_infos = (0..bone_clip_count).map(|_| {
+            let bone_index = file.read_u16()?;
+            let track_flags = file.read_u16()?;
+            let bone_hash = file.read_u32()?;
+            if version < 456 {
+                let _ = file.read_f32()?;
+                let _ = file.read_u32()?;
+            }
+            let track_offset = file.read_u64()?;
+            Ok((bone_index, track_flags, bone_hash, track_offset))
+        }).collect::<Result<Vec<_>>>()?;
+
+        let clips = clip_infos.into_iter().map(|(bone_index, track_flags, bone_hash, track_offset)| {
+            file.seek(SeekFrom::Start(base + track_offset))?;
+            let mut tracks = Vec::new();
+            for (flag, kind) in [
+                (TRACK_TRANSLATION, TrackKind::Translation),
+                (TRACK_ROTATION, TrackKind::Rotation),
+                (TRACK_SCALE, TrackKind::Scale),
+            ] {
+                if track_flags & flag != 0 {
+                    tracks.push(MotTrack::new(file, base, version, kind)?);
+                }
+            }
+            Ok(MotBoneClip { bone_index, bone_hash, tracks })
+        }).collect::<Result<Vec<_>>>()?;
+
+        // the clip table holds an offset per embedded CLIP, a clip that can't be read is left out
+        // so the bone tracks still export
+        let mut events = Vec::new();
+        if clip_offset != 0 && clip_count != 0 {
+            file.seek(SeekFrom::Start(base + clip_offset))?;
+            let offsets = (0..clip_count).map(|_| file.read_u64()).collect::<Result<Vec<_>>>()?;
+            for offset in offsets {
+                match MotEventClip::new(file, base + offset) {
+                    Ok(clip) => events.push(clip),
+                    Err(e) => eprintln!("[WARNING] {name}: skipped event clip at {:#x}: {e}", base + offset),
+                }
+            }
+        }
+
+        Ok(Mot {
+            version,
+            name,
+            frame_count,
+            blending,
+            frame_rate,
+            bones,
+            clips,
+            events,
+        })
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug)]
+pub struct MotList {
+    pub version: u32,
+    pub name: String,
+    pub mots: Vec<Mot>,
+}
+
+impl MotList {
+    pub fn new<F: Read + Seek>(mut file: F) -> Result<MotList> {
+        let version = file.read_u32()?;
+        let magic = file.read_magic()?;
+        let ext = core::str::from_utf8(&magic)?;
+        if ext != "mlst" {
+            return Err(Box::new(MagicError {
+                real_magic: String::from("mlst"),
+                read_magic: ext.to_string()
+            }))
+        }
+        let _ = file.read_u64()?;
+        let pointers_offset = file.read_u64()?;
+        let _motion_ids_offset = file.read_u64()?;
+        let name_offset = file.read_u64()?;
+        if version >= 60 {
+            let _ = file.read_u64()?;
+        }
+        let count = file.read_u32()?;
+
+        file.seek(SeekFrom::Start(name_offset))?;
+        let name = file.read_u16str()?;
+
+        file.seek(SeekFrom::Start(pointers_offset))?;
+        let offsets = (0..count).map(|_| file.read_u64()).collect::<Result<Vec<_>>>()?;
+
+        let mut mots = Vec::new();
+        for offset in offsets {
+            if offset == 0 {
+                continue
+            }
+            // motlists also embed mcam/gmot entries, skip anything that isn't a mot
+            file.seek(SeekFrom::Start(offset + 4))?;
+            if &file.read_magic()? != b"mot " {
+                continue
+            }
+            mots.push(Mot::new_at(&mut file, offset)?);
+        }
+
+        Ok(MotList {
+            version,
+            name,
+            mots,
+        })
+    }
+
+    pub fn skeleton(&self) -> Option<&[MotBone]> {
+        self.mots.iter().find(|mot| !mot.bones.is_empty()).map(|mot| mot.bones.as_slice())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    // a CLIP block at 0x10 with one track holding a container over two keyed properties
+    fn clip_block() -> Vec<u8> {
+        let mut b = vec![0xCCu8; 0x10];
+        let u32s = |b: &mut Vec<u8>, v: &[u32]| v.iter().for_each(|v| b.extend(v.to_le_bytes()));
+        let u64s = |b: &mut Vec<u8>, v: &[u64]| v.iter().for_each(|v| b.extend(v.to_le_bytes()));
+        b.extend(b"CLIP");
+        u32s(&mut b, &[62]);
+        b.extend(90.0f32.to_le_bytes());
+        u32s(&mut b, &[1, 3, 3]);
+        u64s(&mut b, &[0x40, 0x60, 0xF0, 0x150, 0x160]);
+        // track
+        u32s(&mut b, &[0, 1]);
+        u64s(&mut b, &[0, 0, 0]);
+        // properties: (type, child start, child count, name offset)
+        for (ty, start, count, name) in [(1u8, 1u64, 2u16, 0u64), (5, 0, 2, 0), (2, 2, 1, 6)] {
+            b.extend(10.0f32.to_le_bytes());
+            b.extend(20.0f32.to_le_bytes());
+            u32s(&mut b, &[0, 0]);
+            u64s(&mut b, &[name, 0, start]);
+            b.extend(count.to_le_bytes());
+            b.extend([0, 0, 0, 0, ty, 0]);
+        }
+        // keys
+        for (frame, value) in [(10.0f32, 7u64), (15.0, 8), (20.0, 1)] {
+            b.extend(frame.to_le_bytes());
+            b.extend(1.0f32.to_le_bytes());
+            u32s(&mut b, &[1, 0]);
+            u64s(&mut b, &[value, 0]);
+        }
+        b.extend(b"Sound\0");
+        b.resize(0x10 + 0x160, 0);
+        for c in "Se\0Flag\0".encode_utf16() {
+            b.extend(c.to_le_bytes());
+        }
+        b
+    }
+
+    #[test]
+    fn event_clip() {
+        let clip = MotEventClip::new(&mut Cursor::new(clip_block()), 0x10).unwrap();
+        assert_eq!((clip.version, clip.frame_count, clip.tracks.len()), (62, 90.0, 1));
+        let track = &clip.tracks[0];
+        assert_eq!(track.name, "Sound");
+        let summary: Vec<_> = track.properties.iter()
+            .map(|p| (p.name.as_str(), p.property_type, p.keys.iter().map(|k| (k.frame, k.value)).collect::<Vec<_>>()))
+            .collect();
+        assert_eq!(summary, [("Se", 5, vec![(10.0, 7), (15.0, 8)]), ("Flag", 2, vec![(20.0, 1)])]);
+    }
+
+    #[test]
+    fn event_clip_out_of_bounds_keys() {
+        let mut block = clip_block();
+        // the last property claims keys 2..4 of 3
+        let count_at = 0x10 + 0x60 + 2 * 0x30 + 0x28;
+        block[count_at] = 2;
+        assert!(MotEventClip::new(&mut Cursor::new(block), 0x10).is_err());
+    }
+}
@@ -1,8 +1,8 @@
 use core::str;
-use std::{collections::HashMap, io::{Error, ErrorKind, Result, Write}, sync::OnceLock};
+use std::{borrow::Cow, collections::HashMap, io::{Cursor, Error, ErrorKind, Read, Result, Seek, SeekFrom, Write}, sync::OnceLock};
 
 use indexmap::IndexMap;
-use serde::{ser::SerializeSeq, Serialize};
+use serde::{ser::SerializeSeq, Deserialize, Serialize};
 use serde_json::json;
 use uuid::Uuid;
 
@@ -12,13 +12,13 @@ const KEY: [u8; 16] = [207, 206, 251, 248, 236, 10, 51, 102, 147, 169, 29, 147,
 
 #[derive(Debug)]
 #[allow(unused)]
-struct Entry {
-    unkn: u32,
-    guid: [u8; 16],
-    hash: u32,
-    name: String,
-    attributes: Vec<MsgAttribute>,
-    content: Vec<String>,
+pub struct Entry {
+    pub unkn: u32,
+    pub guid: [u8; 16],
+    pub hash: u32,
+    pub name: String,
+    pub attributes: Vec<MsgAttribute>,
+    pub content: Vec<String>,
 }
 
 #[derive(Debug)]
@@ -61,8 +61,114 @@ impl Serialize for MsgAttribute {
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum MsgToken<'a> {
+    Text(&'a str),
+    // <COLOR FF0000>, </COLOR>, <REF ...>, <ICON ...>
+    Tag { name: &'a str, args: Vec<&'a str>, closing: bool },
+    // {0}, {name}
+    Placeholder(&'a str),
+}
+
+// tags that only change how text looks, everything else is treated as a placeholder
+const STYLE_TAGS: [&str; 6] = ["COLOR", "SIZE", "FONT", "RUBY", "B", "I"];
+
+pub fn tokenize(content: &str) -> Vec<MsgToken<'_>> {
+    let mut tokens = Vec::new();
+    let mut text_start = 0;
+    let mut i = 0;
+    let bytes = content.as_bytes();
+    while i < bytes.len() {
+        let end = match bytes[i] {
+            b'<' => content[i..].find('>').map(|end| i + end).filter(|&end| {
+                let inner = &content[i + 1..end];
+                let inner = inner.strip_prefix('/').unwrap_or(inner);
+                inner.starts_with(|c: char| c.is_ascii_alphabetic()) && !inner.contains('<')
+            }),
+            b'{' => content[i..].find('}').map(|end| i + end).filter(|&end| {
+                let inner = &content[i + 1..end];
+                !inner.is_empty() && !inner.contains(|c: char| c.is_whitespace() || c == '{')
+            }),
+            _ => None,
+        };
+        let Some(end) = end else {
+            i += 1;
+            continue
+        };
+        if text_start < i {
+            tokens.push(MsgToken::Text(&content[text_start..i]));
+        }
+        let inner = &content[i + 1..end];
+        if bytes[i] == b'{' {
+            tokens.push(MsgToken::Placeholder(inner));
+        } else {
+            let (closing, inner) = match inner.strip_prefix('/') {
+                Some(inner) => (true, inner),
+                None => (false, inner),
+            };
+            let mut parts = inner.split_whitespace();
+            let name = parts.next().unwrap_or("");
+            tokens.push(MsgToken::Tag { name, args: parts.collect(), closing });
+        }
+        i = end + 1;
+        text_start = i;
+    }
+    if text_start < content.len() {
+        tokens.push(MsgToken::Text(&content[text_start..]));
+    }
+    tokens
+}
+
+pub fn strip_tags(content: &str) -> String {
+    tokenize(content).into_iter()
+        .filter_map(|token| match token {
+            MsgToken::Text(text) => Some(text),
+            _ => None,
+        })
+        .collect()
+}
+
+// sorted list of every placeholder and non style tag, as written in the text
+pub fn placeholders(content: &str) -> Vec<String> {
+    let mut found: Vec<String> = tokenize(content).into_iter()
+        .filter_map(|token| match token {
+            MsgToken::Placeholder(p) => Some(format!("{{{p}}}")),
+            MsgToken::Tag { name, args, closing: false } if !STYLE_TAGS.contains(&name) => {
+                Some(format!("<{}>", std::iter::once(name).chain(args).collect::<Vec<_>>().join(" ")))
+            },
+            _ => None,
+        })
+        .collect();
+    found.sort();
+    found
+}
+
+// items of a that are not in b, both sorted
+fn multiset_diff(a: &[String], b: &[String]) -> Vec<String> {
+    let mut b = b.iter().peekable();
+    let mut diff = Vec::new();
+    for item in a {
+        while b.next_if(|other| *other < item).is_some() {}
+        if b.next_if(|other| *other == item).is_none() {
+            diff.push(item.clone());
+        }
+    }
+    diff
+}
+
+#[derive(Debug)]
+pub struct PlaceholderMismatch {
+    pub name: String,
+    pub lang: usize,
+    pub missing: Vec<String>,
+    pub extra: Vec<String>,
+}
+
 #[derive(Debug, Default)]
 pub struct Msg {
+    version: u32,
+    languages: Vec<u32>,
+    unkn_data: u64,
     attribute_headers: Vec<MsgAttributeHeader>,
     entries: Vec<Entry>,
 }
@@ -83,22 +189,53 @@ pub struct MsgHeader {
     type_name_offset: u64
 }
 
+// GMSG layouts by version. Every known version has the same entry layout (guid, u32 unknown,
+// u32 hash, name offset, attributes offset, then a string offset per language), they differ
+// in the header and the string pool.
+#[derive(Debug, Clone, Copy)]
+struct MsgLayout {
+    // the header has a data offset and the string pool after it is encrypted
+    encrypted: bool,
+}
+
+impl MsgLayout {
+    fn from_version(version: u32) -> Result<MsgLayout> {
+        match version {
+            // RE7 and its later re-release, strings are read in place from the file
+            12 | 0x2022033D => Ok(MsgLayout { encrypted: false }),
+            13 | 14 | 15 | 17 | 20 | 22 => Ok(MsgLayout { encrypted: true }),
+            // Wilds
+            23 => Ok(MsgLayout { encrypted: true }),
+            _ => Err(Error::new(ErrorKind::Unsupported, format!("Unsupported GMSG version {version} ({version:#x})"))),
+        }
+    }
+}
+
+fn data_index(offset: u64, data_offset: u64) -> Result<usize> {
+    offset.checked_sub(data_offset).map(|i| i as usize).ok_or_else(|| {
+        Error::new(ErrorKind::InvalidData, format!("String offset {offset:#x} is before the string data at {data_offset:#x}"))
+    })
+}
+
 impl Msg {
     pub fn new(file_name: String) -> Result<Msg> {
         let mut file = BytesFile::new(file_name)?;
-        let _version = file.read::<u32>()?;
+        let version = file.read::<u32>()?;
         let magic = file.readn::<u8, 4>()?;
-        let magic = str::from_utf8(&magic).unwrap();
+        let magic = String::from_utf8_lossy(&magic);
         if magic != "GMSG" {
-            return Err(Error::new(ErrorKind::Other, format!("Invalid Magic {magic}, {_version}")))
+            return Err(Error::other(format!("Invalid Magic {magic}, {version}")))
         }
 
+        let layout = MsgLayout::from_version(version)?;
+
         let _header_offset = file.read::<u64>()?;
         let entry_count = file.read::<u32>()?;
         let attr_count = file.read::<u32>()?;
         let lang_count = file.read::<u32>()?;
         file.read::<u32>()?; // null
-        let data_offset = file.read::<u64>()?;
+        // unencrypted strings are read straight from the file, so offsets stay absolute
+        let data_offset = if layout.encrypted { file.read::<u64>()? } else { 0 };
         let p_offset = file.read::<u64>()?;
         let lang_offset = file.read::<u64>()?;
         let attr_type_offset = file.read::<u64>()?;
@@ -106,19 +243,25 @@ impl Msg {
         let base_entry_offset = file.index;
         //println!("{entry_count}, {attr_count}, {lang_count}");
 
+        if data_offset as usize > file.len() {
+            return Err(Error::new(ErrorKind::InvalidData, format!("Data offset {data_offset:#x} is past the end of the file ({:#x})", file.len())))
+        }
+
         // Read Data
         file.index = data_offset as usize;
         let mut data = file.read_bytes_to_vec(file.len() - data_offset as usize)?;
-        let mut b = 0;
-        let mut num = 0;
-        let mut num2 = 0;
-        while num < data.len() {
-            let b2 = b;
-            b = data[num2];
-            let num3 = num & 0xf;
-            num += 1;
-            data[num2] = b2 ^ b ^ KEY[num3];
-            num2 = num;
+        if layout.encrypted {
+            let mut b = 0;
+            let mut num = 0;
+            let mut num2 = 0;
+            while num < data.len() {
+                let b2 = b;
+                b = data[num2];
+                let num3 = num & 0xf;
+                num += 1;
+                data[num2] = b2 ^ b ^ KEY[num3];
+                num2 = num;
+            }
         }
 
         let mut data = BytesFile {
@@ -129,10 +272,10 @@ impl Msg {
         // PUT A CHECK HERE FOR IF ITS A VALID FILE OR NOT
 
         file.index = lang_offset as usize;
-        let _languages = (0..lang_count).map(|_| file.read::<u32>()).collect::<Result<Vec<_>>>()?;
+        let languages = (0..lang_count).map(|_| file.read::<u32>()).collect::<Result<Vec<_>>>()?;
 
         file.index = p_offset as usize;
-        let _p = file.read::<u64>()?; // idk what this does
+        let unkn_data = file.read::<u64>()?; // idk what this does
 
         file.index = attr_type_offset as usize;
         let attr_types = (0..attr_count).map(
@@ -161,11 +304,11 @@ impl Msg {
             let name = file.read::<u64>()?;
             let attributes_offset = file.read::<u64>()?;
 
-            let name = data.read_utf16(name as usize - data_offset as usize)?;
+            let name = data.read_utf16(data_index(name, data_offset)?)?;
 
             let content = (0..lang_count).map(|_| {
-                let offset = file.read::<u64>()? as usize;
-                Ok(data.read_utf16(offset - data_offset as usize).unwrap_or("".to_string()))
+                let offset = file.read::<u64>()?;
+                Ok(data.read_utf16(data_index(offset, data_offset)?).unwrap_or("".to_string()))
             }).collect::<Result<Vec<_>>>()?;
 
             file.index = attributes_offset as usize;
@@ -176,11 +319,11 @@ impl Msg {
                         0 => Ok(MsgAttribute::Int(attr as i64)),
                         1 => Ok(MsgAttribute::Float(f64::from_bits(attr))),
                         2 => {
-                            let x = data.read_utf16((attr -data_offset) as usize)?;
+                            let x = data.read_utf16(data_index(attr, data_offset)?)?;
                             Ok(MsgAttribute::String(x))
                         },
                         -1 => Ok(MsgAttribute::Unknown(attr)),
-                        _ => Err(Error::new(ErrorKind::Other, "Unknown attribute type")),
+                        _ => Err(Error::other("Unknown attribute type")),
                     }
                 }).collect::<Result<Vec<_>>>()?;
             entries.push(Entry { name, guid, unkn: _unkn, hash, attributes, content });
@@ -192,12 +335,15 @@ impl Msg {
             .iter()
             .zip(&attr_names)
             .map(|(&ty, name)| {
-                let name = data.read_utf16((name - data_offset) as usize)?;
+                let name = data.read_utf16(data_index(*name, data_offset)?)?;
                 Ok(MsgAttributeHeader{ty, name})
             })
         .collect::<Result<Vec<_>>>()?;
 
         Ok(Msg {
+            version,
+            languages,
+            unkn_data,
             entries,
             attribute_headers,
         })
@@ -246,13 +392,93 @@ impl Msg {
             hashmap
         })
     }
-    pub fn save(&self, writer: &mut dyn Write) {
+    pub fn lang_index(name: &str) -> Option<usize> {
+        Msg::lang_map().iter()
+            .find(|(_, lang)| lang.as_str() == name)
+            .map(|(i, _)| i.parse().unwrap())
+            .or_else(|| name.strip_prefix("lang")?.parse().ok())
+    }
+
+    pub fn entries(&self) -> &Vec<Entry> {
+        &self.entries
+    }
+
+    // ids missing from the map become lang<id>, "Unknown" is already language 33
+    pub fn lang_name(lang: usize) -> Cow<'static, str> {
+        match Msg::lang_map().get(&lang.to_string()) {
+            Some(name) => Cow::Borrowed(name.as_str()),
+            None => Cow::Owned(format!("lang{lang}")),
+        }
+    }
+
+    // language ids from the file's language table, entry content is in the same order
+    pub fn languages(&self) -> Vec<usize> {
+        self.languages.iter().map(|&lang| lang as usize).collect()
+    }
+
+    fn lang_position(&self, lang: usize) -> Option<usize> {
+        self.languages.iter().position(|&l| l as usize == lang)
+    }
+
+    // (uuid, name, (language, text) in file order)
+    pub fn texts(&self) -> impl Iterator<Item = (String, &str, Vec<(usize, &str)>)> {
+        self.entries.iter().map(|entry| {
+            let content = self.languages.iter().zip(&entry.content)
+                .map(|(&lang, text)| (lang as usize, text.as_str()))
+                .collect();
+            (Uuid::from_bytes_le(entry.guid).to_string(), entry.name.as_str(), content)
+        })
+    }
+
+    // key can be either the entry's uuid or its name, returns false if nothing matched
+    pub fn set_text(&mut self, key: &str, lang: usize, text: String) -> bool {
+        let Some(lang) = self.lang_position(lang) else { return false };
+        let entry = self.entries.iter_mut().find(|entry| {
+            entry.name == key || Uuid::from_bytes_le(entry.guid).to_string() == key
+        });
+        match entry {
+            Some(entry) if lang < entry.content.len() => {
+                entry.content[lang] = text;
+                true
+            },
+            _ => false,
+        }
+    }
+
+    // source language entries that have placeholders not matching the other languages
+    pub fn check_placeholders(&self, source: usize) -> Vec<PlaceholderMismatch> {
+        let mut mismatches = Vec::new();
+        let Some(source) = self.lang_position(source) else { return mismatches };
+        for entry in &self.entries {
+            let Some(source_text) = entry.content.get(source) else { continue };
+            let expected = placeholders(source_text);
+            for (lang, text) in entry.content.iter().enumerate() {
+                // empty means untranslated, that's not something to fix here
+                if lang == source || text.is_empty() {
+                    continue
+                }
+                let found = placeholders(text);
+                let missing: Vec<String> = multiset_diff(&expected, &found);
+                let extra: Vec<String> = multiset_diff(&found, &expected);
+                if !missing.is_empty() || !extra.is_empty() {
+                    let lang = self.languages[lang] as usize;
+                    mismatches.push(PlaceholderMismatch { name: entry.name.clone(), lang, missing, extra });
+                }
+            }
+        }
+        mismatches
+    }
+
+    // langs keeps only those languages (in file order), None writes all of them
+    pub fn save(&self, writer: &mut dyn Write, strip_markup: bool, langs: Option<&[usize]>) {
+        let keep = |lang: &u32| langs.is_none_or(|langs| langs.contains(&(*lang as usize)));
         #[derive(Debug, Serialize)]
         struct EntryInfo<'a> {
             name: &'a str,
             hash: u32,
+            unkn: u32,
             attributes: &'a Vec<MsgAttribute>,
-            content: IndexMap<&'a str, String>,
+            content: IndexMap<Cow<'static, str>, String>,
         }
         let name_to_uuid_map: IndexMap<_, _> = self.entries.iter()
             .map(|entry| {
@@ -263,17 +489,23 @@ impl Msg {
             .map(|entry| {
                 let uuid = Uuid::from_bytes_le(entry.guid).to_string();
                 //println!("{:?}", entry.content);
-                let content = entry.content.iter().enumerate()
-                        .map(|(i, c)| {
-                            let enum_name = Msg::lang_map().get(&i.to_string()).unwrap();
+                let content = self.languages.iter().zip(&entry.content)
+                        .filter(|(lang, _)| keep(lang))
+                        .map(|(&lang, c)| {
+                            let enum_name = Msg::lang_name(lang as usize);
                             //println!("{}, {}", &enum_name, &c);
                             //c.to_string()
-                            (enum_name.as_str(), c.clone())
+                            if strip_markup {
+                                (enum_name, strip_tags(c))
+                            } else {
+                                (enum_name, c.clone())
+                            }
                         })
                         .collect();
                 ( uuid, EntryInfo {
                     name: &entry.name,
                     hash: entry.hash,
+                    unkn: entry.unkn,
                     attributes: &entry.attributes,
                     content
                 })
@@ -281,17 +513,308 @@ impl Msg {
         //println!("{:#?}", msgs.get_index(66));
         #[derive(Serialize)]
         struct Data<'a> {
+            version: u32,
+            languages: Vec<u32>,
+            unkn_data: u64,
             msgs: IndexMap<String, EntryInfo<'a>>,
             attributes: &'a Vec<MsgAttributeHeader>,
+            attribute_types: Vec<i32>,
             name_to_uuid: IndexMap<&'a String, String>,
         }
         serde_json::to_writer_pretty(writer, 
             &Data {
+                version: self.version,
+                languages: self.languages.iter().copied().filter(keep).collect(),
+                unkn_data: self.unkn_data,
                 msgs,
                 attributes: &self.attribute_headers,
+                attribute_types: self.attribute_headers.iter().map(|h| h.ty).collect(),
                 name_to_uuid: name_to_uuid_map,
             }
         ).unwrap();
         //serde_json::to_writer_pretty(writer, &json_map).unwrap();
     }
+
+    // Rebuilds a Msg from the json written by save
+    pub fn from_json<R: Read>(reader: R, default_version: u32) -> Result<Msg> {
+        #[derive(Deserialize)]
+        struct EntryJson {
+            name: String,
+            hash: u32,
+            #[serde(default)]
+            unkn: u32,
+            attributes: Vec<serde_json::Value>,
+            content: IndexMap<String, String>,
+        }
+        #[derive(Deserialize)]
+        struct DataJson {
+            version: Option<u32>,
+            languages: Option<Vec<u32>>,
+            #[serde(default)]
+            unkn_data: u64,
+            msgs: IndexMap<String, EntryJson>,
+            attributes: Vec<Option<String>>,
+            attribute_types: Option<Vec<i32>>,
+        }
+        let data: DataJson = serde_json::from_reader(reader)?;
+
+        // dumps from before the language table was saved had content in language id order
+        let lang_count = data.msgs.values()
+            .flat_map(|entry| entry.content.keys())
+            .map(|name| Msg::lang_index(name).map(|i| i + 1).ok_or(name))
+            .try_fold(0, |max, i| i.map(|i| usize::max(max, i)))
+            .map_err(|name| Error::new(ErrorKind::InvalidData, format!("Unknown language {name}")))?;
+        let languages = data.languages.unwrap_or_else(|| (0..lang_count as u32).collect());
+
+        // older dumps have no types, guess them from the first entry's values
+        let attribute_types = match data.attribute_types {
+            Some(types) => types,
+            None => (0..data.attributes.len()).map(|i| {
+                match data.msgs.values().next().and_then(|entry| entry.attributes.get(i)) {
+                    Some(serde_json::Value::String(_)) => 2,
+                    Some(serde_json::Value::Number(n)) if n.is_f64() => 1,
+                    _ => 0,
+                }
+            }).collect(),
+        };
+        if attribute_types.len() != data.attributes.len() {
+            return Err(Error::new(ErrorKind::InvalidData, "attribute_types and attributes differ in length"))
+        }
+        let attribute_headers = attribute_types.iter().zip(data.attributes)
+            .map(|(&ty, name)| MsgAttributeHeader { ty, name: name.unwrap_or_default() })
+            .collect();
+
+        let entries = data.msgs.into_iter().map(|(uuid, entry)| {
+            let guid = Uuid::parse_str(&uuid)
+                .map_err(|e| Error::new(ErrorKind::InvalidData, format!("{uuid}: {e}")))?
+                .to_bytes_le();
+            let mut content = vec![String::new(); languages.len()];
+            for (lang, text) in entry.content {
+                match Msg::lang_index(&lang).and_then(|i| languages.iter().position(|&l| l as usize == i)) {
+                    Some(i) => content[i] = text,
+                    _ => return Err(Error::new(ErrorKind::InvalidData, format!("{uuid}: language {lang} not in file"))),
+                }
+            }
+            let attributes = entry.attributes.iter().zip(&attribute_types).map(|(value, ty)| {
+                Ok(match (ty, value) {
+                    (0, serde_json::Value::Number(n)) if n.is_i64() => MsgAttribute::Int(n.as_i64().unwrap()),
+                    (1, serde_json::Value::Number(n)) => MsgAttribute::Float(n.as_f64().unwrap()),
+                    (2, serde_json::Value::String(v)) => MsgAttribute::String(v.clone()),
+                    (-1, serde_json::Value::Number(n)) if n.is_u64() => MsgAttribute::Unknown(n.as_u64().unwrap()),
+                    _ => return Err(Error::new(ErrorKind::InvalidData, format!("{uuid}: attribute {value} does not match type {ty}"))),
+                })
+            }).collect::<Result<Vec<_>>>()?;
+            Ok(Entry {
+                unkn: entry.unkn,
+                guid,
+                hash: entry.hash,
+                name: entry.name,
+                attributes,
+                content,
+            })
+        }).collect::<Result<Vec<_>>>()?;
+
+        Ok(Msg {
+            version: data.version.unwrap_or(default_version),
+            languages,
+            unkn_data: data.unkn_data,
+            attribute_headers,
+            entries,
+        })
+    }
+
+    // Lays the file out the same way Msg::new reads it, with the string pool encrypted at the end
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
+        fn align<W: Write + Seek>(f: &mut W, align: u64) -> Result<()> {
+            let pos = f.stream_position()?;
+            let pad = (align - pos % align) % align;
+            f.write_all(&vec![0; pad as usize])
+        }
+
+        let mut pool: Vec<u16> = Vec::new();
+        let mut pool_offsets: HashMap<&str, u64> = HashMap::new();
+        let mut strings: Vec<&str> = Vec::new();
+        for header in &self.attribute_headers {
+            strings.push(&header.name);
+        }
+        for entry in &self.entries {
+            strings.push(&entry.name);
+            strings.extend(entry.content.iter().map(|c| c.as_str()));
+            for attr in &entry.attributes {
+                if let MsgAttribute::String(v) = attr {
+                    strings.push(v);
+                }
+            }
+        }
+        for s in strings {
+            if !pool_offsets.contains_key(s) {
+                pool_offsets.insert(s, pool.len() as u64 * 2);
+                pool.extend(s.encode_utf16());
+                pool.push(0);
+            }
+        }
+
+        let entry_size = 16 + 4 + 4 + 8 + 8 + self.languages.len() as u64 * 8;
+        let attr_count = self.attribute_headers.len() as u64;
+        let layout = MsgLayout::from_version(self.version)?;
+        let header_size = if layout.encrypted { 0x48 } else { 0x40 } + self.entries.len() as u64 * 8;
+        let p_offset = header_size;
+        let lang_offset = p_offset + 8;
+        let attr_type_offset = align_to(lang_offset + self.languages.len() as u64 * 4, 8);
+        let attr_type_name_offset = align_to(attr_type_offset + attr_count * 4, 8);
+        let entries_offset = attr_type_name_offset + attr_count * 8;
+        let entry_stride = entry_size + attr_count * 8;
+        let data_offset = align_to(entries_offset + self.entries.len() as u64 * entry_stride, 16);
+        let string_offset = |s: &str| data_offset + pool_offsets[s];
+
+        let mut f = Cursor::new(Vec::new());
+        f.write_all(&self.version.to_le_bytes())?;
+        f.write_all(b"GMSG")?;
+        f.write_all(&0x10u64.to_le_bytes())?;
+        f.write_all(&(self.entries.len() as u32).to_le_bytes())?;
+        f.write_all(&(attr_count as u32).to_le_bytes())?;
+        f.write_all(&(self.languages.len() as u32).to_le_bytes())?;
+        f.write_all(&0u32.to_le_bytes())?;
+        if layout.encrypted {
+            f.write_all(&data_offset.to_le_bytes())?;
+        }
+        for offset in [p_offset, lang_offset, attr_type_offset, attr_type_name_offset] {
+            f.write_all(&offset.to_le_bytes())?;
+        }
+        for i in 0..self.entries.len() as u64 {
+            f.write_all(&(entries_offset + i * entry_stride).to_le_bytes())?;
+        }
+
+        f.write_all(&self.unkn_data.to_le_bytes())?;
+        for lang in &self.languages {
+            f.write_all(&lang.to_le_bytes())?;
+        }
+        align(&mut f, 8)?;
+        for header in &self.attribute_headers {
+            f.write_all(&header.ty.to_le_bytes())?;
+        }
+        align(&mut f, 8)?;
+        for header in &self.attribute_headers {
+            f.write_all(&string_offset(&header.name).to_le_bytes())?;
+        }
+
+        for (i, entry) in self.entries.iter().enumerate() {
+            let entry_offset = entries_offset + i as u64 * entry_stride;
+            f.seek(SeekFrom::Start(entry_offset))?;
+            f.write_all(&entry.guid)?;
+            f.write_all(&entry.unkn.to_le_bytes())?;
+            f.write_all(&entry.hash.to_le_bytes())?;
+            f.write_all(&string_offset(&entry.name).to_le_bytes())?;
+            f.write_all(&(entry_offset + entry_size).to_le_bytes())?;
+            if entry.content.len() != self.languages.len() {
+                return Err(Error::new(ErrorKind::InvalidData, format!("{} has {} languages, expected {}", entry.name, entry.content.len(), self.languages.len())))
+            }
+            for content in &entry.content {
+                f.write_all(&string_offset(content).to_le_bytes())?;
+            }
+            for attr in &entry.attributes {
+                let v = match attr {
+                    MsgAttribute::Int(v) => *v as u64,
+                    MsgAttribute::Float(v) => v.to_bits(),
+                    MsgAttribute::String(v) => string_offset(v),
+                    MsgAttribute::Unknown(v) => *v,
+                };
+                f.write_all(&v.to_le_bytes())?;
+            }
+        }
+        align(&mut f, 16)?;
+        if f.stream_position()? != data_offset {
+            return Err(Error::other("Msg data offset mismatch"))
+        }
+
+        let mut data: Vec<u8> = pool.iter().flat_map(|c| c.to_le_bytes()).collect();
+        if layout.encrypted {
+            let mut prev = 0;
+            for (i, b) in data.iter_mut().enumerate() {
+                *b = *b ^ prev ^ KEY[i & 0xf];
+                prev = *b;
+            }
+        }
+        f.write_all(&data)?;
+
+        writer.write_all(&f.into_inner())
+    }
+}
+
+fn align_to(v: u64, align: u64) -> u64 {
+    v + (align - v % align) % align
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_json(version: u32) -> String {
+        json!({
+            "version": version,
+            "languages": [1, 0, 33],
+            "unkn_data": 0x1234,
+            "msgs": {
+                "00112233-4455-6677-8899-aabbccddeeff": {
+                    "name": "Test_Name_00",
+                    "hash": 0xDEADBEEFu32,
+                    "unkn": 7,
+                    "attributes": [-3, 0.5, "attr <COL RED>text</COL>", 42],
+                    "content": {
+                        "English": "Hello {0}\r\nsecond line",
+                        "Japanese": "こんにちは",
+                        "Unknown": "",
+                    },
+                },
+                "ffeeddcc-bbaa-9988-7766-554433221100": {
+                    "name": "Test_Name_01",
+                    "hash": 1,
+                    "unkn": 0,
+                    "attributes": [0, -1.25, "", 0],
+                    "content": {
+                        "English": "Hello {0}\r\nsecond line",
+                        "Japanese": "",
+                        "Unknown": "shared <PL>",
+                    },
+                },
+            },
+            "attributes": ["int", "float", "string", "unknown"],
+            "attribute_types": [0, 1, 2, -1],
+        }).to_string()
+    }
+
+    // 12 stores the strings as they are, 23 (Wilds) encrypts them
+    #[test]
+    fn write_read_round_trip() {
+        for version in [12, 23] {
+            let msg = Msg::from_json(sample_json(version).as_bytes(), 0).unwrap();
+            let path = std::env::temp_dir().join(format!("mhwsgen_test_{}.msg.{version}", std::process::id()));
+            let mut file = std::fs::File::create(&path).unwrap();
+            msg.write(&mut file).unwrap();
+            drop(file);
+            let read = Msg::new(path.to_string_lossy().to_string()).unwrap();
+            std::fs::remove_file(&path).unwrap();
+
+            assert_eq!(read.version, version);
+            assert_eq!(read.languages, vec![1, 0, 33]);
+            assert_eq!(read.entries[0].content, vec!["Hello {0}\r\nsecond line", "こんにちは", ""]);
+            assert_eq!(read.entries[1].content, vec!["Hello {0}\r\nsecond line", "", "shared <PL>"]);
+            assert!(matches!(&read.entries[0].attributes[..], [
+                MsgAttribute::Int(-3), MsgAttribute::Float(0.5), MsgAttribute::String(s), MsgAttribute::Unknown(42)
+            ] if s == "attr <COL RED>text</COL>"));
+            // everything else, guids, hashes and attribute headers included
+            assert_eq!(format!("{read:?}"), format!("{msg:?}"), "version {version}");
+        }
+    }
+
+    #[test]
+    fn unknown_versions_are_unsupported() {
+        for version in [0, 11, 16, 24, 0xFF, 0x2022033E] {
+            let err = MsgLayout::from_version(version).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::Unsupported);
+            assert!(err.to_string().contains(&format!("Unsupported GMSG version {version}")), "{err}");
+        }
+        let msg = Msg::from_json(sample_json(16).as_bytes(), 0).unwrap();
+        assert_eq!(msg.write(&mut Vec::new()).unwrap_err().kind(), ErrorKind::Unsupported);
+    }
 }
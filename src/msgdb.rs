@@ -0,0 +1,125 @@
+use std::path::{Path, PathBuf};
+
+use indexmap::IndexMap;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::msg::{Msg, MsgAttribute};
+use crate::reerr::Result;
+use crate::translation;
+
+#[derive(Debug, Serialize)]
+pub struct MsgDbEntry {
+    pub name: String,
+    pub hash: u32,
+    pub file: String,
+    pub attributes: Vec<MsgAttribute>,
+    pub content: IndexMap<String, String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictKind {
+    // same guid, different name or text
+    Guid,
+    // same name, different guid
+    Name,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Conflict {
+    pub kind: ConflictKind,
+    pub key: String,
+    pub kept: String,
+    pub dropped: String,
+}
+
+// Every entry from a set of msg files, first file to define a guid wins
+#[derive(Debug, Default, Serialize)]
+pub struct MsgDb {
+    pub msgs: IndexMap<String, MsgDbEntry>,
+    pub name_to_uuid: IndexMap<String, String>,
+    pub conflicts: Vec<Conflict>,
+    #[serde(skip)]
+    langs: Option<Vec<usize>>,
+}
+
+impl MsgDb {
+    pub fn new(langs: Option<Vec<usize>>) -> MsgDb {
+        MsgDb {
+            langs,
+            ..Default::default()
+        }
+    }
+
+    pub fn add(&mut self, msg: &Msg, file: &str) {
+        for entry in msg.entries() {
+            let uuid = Uuid::from_bytes_le(entry.guid).to_string();
+            let content: IndexMap<String, String> = msg.languages().into_iter().zip(&entry.content)
+                .filter(|(lang, _)| self.langs.as_ref().is_none_or(|langs| langs.contains(lang)))
+                .map(|(lang, text)| (translation::lang_name(lang).into_owned(), text.clone()))
+                .collect();
+
+            if let Some(existing) = self.msgs.get(&uuid) {
+                // the same file can be shipped in a few places, only differing copies matter
+                if existing.name != entry.name || existing.content != content {
+                    self.conflicts.push(Conflict {
+                        kind: ConflictKind::Guid,
+                        key: uuid,
+                        kept: existing.file.clone(),
+                        dropped: file.to_string(),
+                    });
+                }
+                continue
+            }
+
+            match self.name_to_uuid.get(&entry.name) {
+                Some(other) if *other != uuid => {
+                    self.conflicts.push(Conflict {
+                        kind: ConflictKind::Name,
+                        key: entry.name.clone(),
+                        kept: self.msgs[other].file.clone(),
+                        dropped: file.to_string(),
+                    });
+                },
+                Some(_) => (),
+                None => {
+                    self.name_to_uuid.insert(entry.name.clone(), uuid.clone());
+                },
+            }
+
+            self.msgs.insert(uuid, MsgDbEntry {
+                name: entry.name.clone(),
+                hash: entry.hash,
+                file: file.to_string(),
+                attributes: entry.attributes.clone(),
+                content,
+            });
+        }
+    }
+}
+
+fn is_msg_file(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else { return false };
+    let split = name.split('.').collect::<Vec<_>>();
+    split.len() == 3 && split[1] == "msg" && split[2].parse::<u32>().is_ok()
+}
+
+// files are read in sorted order so which duplicate wins doesn't depend on the filesystem
+pub fn build(files: Vec<PathBuf>, base_dir: &Path, langs: Option<Vec<usize>>) -> Result<MsgDb> {
+    let mut files: Vec<PathBuf> = files.into_iter().filter(|path| is_msg_file(path)).collect();
+    files.sort();
+    let mut db = MsgDb::new(langs);
+    for path in files {
+        let msg = match Msg::new(path.to_string_lossy().to_string()) {
+            Ok(msg) => msg,
+            Err(e) => {
+                eprintln!("[ERROR] Could not read {path:?}: {e}");
+                continue
+            }
+        };
+        let file = path.strip_prefix(base_dir).unwrap_or(&path).to_string_lossy().replace('\\', "/");
+        db.add(&msg, &file);
+    }
+    Ok(db)
+}
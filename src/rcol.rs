@@ -0,0 +1,365 @@
+use crate::dersz::{serialize_obb, DeRsz, RszType};
+use crate::file_ext::*;
+use crate::rsz::Rsz;
+use crate::reerr::{Result, FileParseError::*};
+use nalgebra_glm::Vec3;
+use serde::{Serialize, Serializer};
+use std::io::{Read, Seek, SeekFrom};
+use uuid::Uuid;
+
+// every shape reserves the same space for its parameters, regardless of the shape type
+const SHAPE_DATA_SIZE: u64 = 0x50;
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub enum ShapeType {
+    Aabb,
+    Sphere,
+    ContinuousSphere,
+    Capsule,
+    ContinuousCapsule,
+    Box,
+    Mesh,
+    HeightField,
+    StaticCompound,
+    Area,
+    Triangle,
+    SkinningMesh,
+    Cylinder,
+    DeformableMesh,
+    Unknown(i32),
+}
+
+impl From<i32> for ShapeType {
+    fn from(v: i32) -> Self {
+        match v {
+            0 => Self::Aabb,
+            1 => Self::Sphere,
+            2 => Self::ContinuousSphere,
+            3 => Self::Capsule,
+            4 => Self::ContinuousCapsule,
+            5 => Self::Box,
+            6 => Self::Mesh,
+            7 => Self::HeightField,
+            8 => Self::StaticCompound,
+            9 => Self::Area,
+            10 => Self::Triangle,
+            11 => Self::SkinningMesh,
+            12 => Self::Cylinder,
+            13 => Self::DeformableMesh,
+            x => Self::Unknown(x),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RcolCapsule {
+    pub p0: Vec3,
+    pub p1: Vec3,
+    pub radius: f32,
+}
+
+// Shape parameters reuse the rsz value representations, except for capsules
+// whose radius is a lone float rather than a third vector
+pub enum RcolShapeData {
+    Rsz(RszType),
+    Capsule(RcolCapsule),
+}
+
+impl Serialize for RcolShapeData {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer {
+            match self {
+                RcolShapeData::Rsz(RszType::AABB(v)) => v.serialize(serializer),
+                RcolShapeData::Rsz(RszType::Sphere(v)) => v.serialize(serializer),
+                RcolShapeData::Rsz(RszType::OBB(v)) => serialize_obb(v, serializer),
+                RcolShapeData::Rsz(RszType::Data(v)) => v.serialize(serializer),
+                RcolShapeData::Rsz(_) => serializer.serialize_none(),
+                RcolShapeData::Capsule(v) => v.serialize(serializer),
+            }
+    }
+}
+
+fn serialize_guid<S: Serializer>(guid: &[u8; 16], serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_str(&Uuid::from_bytes_le(*guid).to_string())
+}
+
+fn serialize_guids<S: Serializer>(guids: &[[u8; 16]], serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    serializer.collect_seq(guids.iter().map(|guid| Uuid::from_bytes_le(*guid).to_string()))
+}
+
+#[derive(Serialize)]
+pub struct RcolShape {
+    #[serde(serialize_with = "serialize_guid")]
+    pub guid: [u8; 16],
+    pub name: String,
+    pub name_hash: u32,
+    pub user_data_index: i32,
+    pub layer_index: i32,
+    pub attribute: i32,
+    pub skip_id_bits: u32,
+    pub ignore_tag_bits: u32,
+    pub primary_joint: String,
+    pub secondary_joint: String,
+    pub shape_type: ShapeType,
+    pub shape: RcolShapeData,
+}
+
+#[derive(Serialize)]
+pub struct RcolGroup {
+    #[serde(serialize_with = "serialize_guid")]
+    pub guid: [u8; 16],
+    pub name: String,
+    pub name_hash: u32,
+    pub layer_index: i32,
+    pub mask_bits: u32,
+    #[serde(serialize_with = "serialize_guid")]
+    pub layer_guid: [u8; 16],
+    #[serde(serialize_with = "serialize_guids")]
+    pub mask_guids: Vec<[u8; 16]>,
+    pub shapes: Vec<RcolShape>,
+}
+
+#[derive(Serialize)]
+pub struct RcolRequestSet {
+    pub id: u32,
+    pub group_index: i32,
+    pub shape_offset: i32,
+    pub status: i32,
+    pub user_data_index: i32,
+    pub name: String,
+    pub key_name: String,
+    pub name_hash: u32,
+    pub key_hash: u32,
+}
+
+#[allow(dead_code)]
+pub struct Rcol {
+    pub groups: Vec<RcolGroup>,
+    pub request_sets: Vec<RcolRequestSet>,
+    pub ignore_tags: Vec<String>,
+    pub rsz: Option<Rsz>,
+}
+
+fn read_string_at<F: Read + Seek>(file: &mut F, offset: u64) -> Result<String> {
+    if offset == 0 {
+        return Ok(String::new())
+    }
+    let pos = file.tell()?;
+    file.seek(SeekFrom::Start(offset))?;
+    let s = file.read_u16str()?;
+    file.seek(SeekFrom::Start(pos))?;
+    Ok(s)
+}
+
+fn read_guid<F: Read>(file: &mut F) -> Result<[u8; 16]> {
+    let mut guid = [0; 16];
+    file.read_exact(&mut guid)?;
+    Ok(guid)
+}
+
+impl RcolShape {
+    fn read_shape<F: Read + Seek>(file: &mut F, shape_type: ShapeType) -> Result<RcolShapeData> {
+        let start = file.tell()?;
+        let shape = match shape_type {
+            ShapeType::Aabb => RcolShapeData::Rsz(RszType::AABB((
+                file.read_f32()?, file.read_f32()?, file.read_f32()?, file.read_f32()?,
+                file.read_f32()?, file.read_f32()?, file.read_f32()?, file.read_f32()?,
+            ))),
+            ShapeType::Sphere | ShapeType::ContinuousSphere => RcolShapeData::Rsz(RszType::Sphere((
+                file.read_f32()?, file.read_f32()?, file.read_f32()?, file.read_f32()?,
+            ))),
+            ShapeType::Capsule | ShapeType::ContinuousCapsule | ShapeType::Cylinder => {
+                let p0 = file.read_f32vec4()?.xyz();
+                let p1 = file.read_f32vec4()?.xyz();
+                let radius = file.read_f32()?;
+                RcolShapeData::Capsule(RcolCapsule { p0, p1, radius })
+            },
+            ShapeType::Box => {
+                let coord = file.read_f32m4x4()?;
+                let extent = file.read_f32vec3()?;
+                RcolShapeData::Rsz(RszType::OBB((coord, extent)))
+            },
+            _ => RcolShapeData::Rsz(RszType::Data(file.read_u8_n(SHAPE_DATA_SIZE as usize)?)),
+        };
+        file.seek(SeekFrom::Start(start + SHAPE_DATA_SIZE))?;
+        Ok(shape)
+    }
+
+    fn new<F: Read + Seek>(file: &mut F) -> Result<RcolShape> {
+        let guid = read_guid(file)?;
+        let name_offset = file.read_u64()?;
+        let name_hash = file.read_u32()?;
+        let user_data_index = file.read_i32()?;
+        let layer_index = file.read_i32()?;
+        let attribute = file.read_i32()?;
+        let skip_id_bits = file.read_u32()?;
+        let ignore_tag_bits = file.read_u32()?;
+        let primary_joint_offset = file.read_u64()?;
+        let secondary_joint_offset = file.read_u64()?;
+        let _primary_joint_hash = file.read_u32()?;
+        let _secondary_joint_hash = file.read_u32()?;
+        let shape_type = ShapeType::from(file.read_i32()?);
+        let _padding = file.read_u32()?;
+        let shape = Self::read_shape(file, shape_type)?;
+
+        Ok(RcolShape {
+            guid,
+            name: read_string_at(file, name_offset)?,
+            name_hash,
+            user_data_index,
+            layer_index,
+            attribute,
+            skip_id_bits,
+            ignore_tag_bits,
+            primary_joint: read_string_at(file, primary_joint_offset)?,
+            secondary_joint: read_string_at(file, secondary_joint_offset)?,
+            shape_type,
+            shape,
+        })
+    }
+}
+
+impl Rcol {
+    pub fn new<F: Read + Seek>(mut file: F, version: u32) -> Result<Rcol> {
+        let magic = file.read_magic()?;
+        let ext = core::str::from_utf8(&magic)?;
+        if ext != "RCOL" {
+            return Err(Box::new(MagicError {
+                real_magic: String::from("RCOL"),
+                read_magic: ext.to_string()
+            }))
+        }
+
+        let group_count = file.read_u32()?;
+        let _shape_count = file.read_u32()?;
+        let _user_data_count = file.read_u32()?;
+        let request_set_count = file.read_u32()?;
+        let _max_request_set_id = file.read_u32()?;
+        let (ignore_tag_count, _auto_joint_count) = if version >= 25 {
+            (file.read_u32()?, file.read_u32()?)
+        } else {
+            (0, 0)
+        };
+        let user_data_size = file.read_u32()?;
+        let _status = file.read_u32()?;
+        if version >= 25 {
+            let _ = file.read_u64()?;
+        }
+        let groups_offset = file.read_u64()?;
+        let data_offset = file.read_u64()?;
+        let request_sets_offset = file.read_u64()?;
+        let (ignore_tags_offset, _auto_joints_offset) = if version >= 25 {
+            (file.read_u64()?, file.read_u64()?)
+        } else {
+            (0, 0)
+        };
+
+        file.seek(SeekFrom::Start(groups_offset))?;
+        let group_infos = (0..group_count).map(|_| {
+            let guid = read_guid(&mut file)?;
+            let name_offset = file.read_u64()?;
+            let name_hash = file.read_u32()?;
+            let _ = file.read_u32()?;
+            let shape_count = file.read_u32()?;
+            let mask_guid_count = file.read_u32()?;
+            let shapes_offset = file.read_u64()?;
+            let layer_index = file.read_i32()?;
+            let mask_bits = file.read_u32()?;
+            let mask_guids_offset = file.read_u64()?;
+            let layer_guid = read_guid(&mut file)?;
+            Ok((guid, name_offset, name_hash, shape_count, mask_guid_count, shapes_offset, layer_index, mask_bits, mask_guids_offset, layer_guid))
+        }).collect::<Result<Vec<_>>>()?;
+
+        let groups = group_infos.into_iter().map(|(guid, name_offset, name_hash, shape_count, mask_guid_count, shapes_offset, layer_index, mask_bits, mask_guids_offset, layer_guid)| {
+            file.seek(SeekFrom::Start(mask_guids_offset))?;
+            let mask_guids = (0..mask_guid_count).map(|_| read_guid(&mut file)).collect::<Result<Vec<_>>>()?;
+            file.seek(SeekFrom::Start(shapes_offset))?;
+            let shapes = (0..shape_count).map(|_| RcolShape::new(&mut file)).collect::<Result<Vec<_>>>()?;
+            Ok(RcolGroup {
+                guid,
+                name: read_string_at(&mut file, name_offset)?,
+                name_hash,
+                layer_index,
+                mask_bits,
+                layer_guid,
+                mask_guids,
+                shapes,
+            })
+        }).collect::<Result<Vec<_>>>()?;
+
+        file.seek(SeekFrom::Start(request_sets_offset))?;
+        let request_sets = (0..request_set_count).map(|_| {
+            let id = file.read_u32()?;
+            let group_index = file.read_i32()?;
+            let shape_offset = file.read_i32()?;
+            let status = file.read_i32()?;
+            let user_data_index = file.read_i32()?;
+            let _group_user_data_index = file.read_i32()?;
+            let _ = file.read_u64()?;
+            let name_offset = file.read_u64()?;
+            let key_name_offset = file.read_u64()?;
+            let name_hash = file.read_u32()?;
+            let key_hash = file.read_u32()?;
+            Ok(RcolRequestSet {
+                id,
+                group_index,
+                shape_offset,
+                status,
+                user_data_index,
+                name: read_string_at(&mut file, name_offset)?,
+                key_name: read_string_at(&mut file, key_name_offset)?,
+                name_hash,
+                key_hash,
+            })
+        }).collect::<Result<Vec<_>>>()?;
+
+        let mut ignore_tags = Vec::new();
+        if ignore_tags_offset != 0 {
+            file.seek(SeekFrom::Start(ignore_tags_offset))?;
+            let offsets = (0..ignore_tag_count).map(|_| {
+                let offset = file.read_u64()?;
+                let _hash = file.read_u32()?;
+                let _ = file.read_u32()?;
+                Ok(offset)
+            }).collect::<Result<Vec<_>>>()?;
+            for offset in offsets {
+                ignore_tags.push(read_string_at(&mut file, offset)?);
+            }
+        }
+
+        let rsz = if user_data_size != 0 {
+            Some(Rsz::new(&mut file, data_offset, data_offset + user_data_size as u64)?)
+        } else {
+            None
+        };
+
+        Ok(Rcol {
+            groups,
+            request_sets,
+            ignore_tags,
+            rsz,
+        })
+    }
+
+    pub fn deserialize(self, root_dir: Option<String>) -> Result<DeRcol> {
+        let user_data = match &self.rsz {
+            Some(rsz) => Some(rsz.deserializev2(root_dir)?),
+            None => None,
+        };
+        Ok(DeRcol {
+            groups: self.groups,
+            request_sets: self.request_sets,
+            ignore_tags: self.ignore_tags,
+            user_data,
+        })
+    }
+}
+
+#[derive(Serialize)]
+pub struct DeRcol {
+    pub groups: Vec<RcolGroup>,
+    pub request_sets: Vec<RcolRequestSet>,
+    pub ignore_tags: Vec<String>,
+    pub user_data: Option<DeRsz>,
+}
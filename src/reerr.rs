@@ -12,9 +12,51 @@ pub enum FileParseError {
     InvalidBool(u8),
     BadAlign(u64, u64),
     InvalidRszTypeHash(u32),
+    OutOfBounds { offset: usize, expected: usize, len: usize },
+    UnexpectedSeek { pos: u64, expected: u64 },
+    UnexpectedAlign { pos: u64, expected: u64, align: u64 },
+    // where another error happened, see Context
+    Context { file: Option<String>, offset: Option<u64>, field: Option<String>, source: Box<dyn Error + Send + Sync> },
 }
 
-impl Error for FileParseError {}
+impl Error for FileParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Context { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl FileParseError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::MagicError { .. } => "MagicError",
+            Self::TexReadError { .. } => "TexReadError",
+            Self::GDeflateError { .. } => "GDeflateError",
+            Self::Unsupported { .. } => "Unsupported",
+            Self::InvalidBool(_) => "InvalidBool",
+            Self::BadAlign(_, _) => "BadAlign",
+            Self::InvalidRszTypeHash(_) => "InvalidRszTypeHash",
+            Self::OutOfBounds { .. } => "OutOfBounds",
+            Self::UnexpectedSeek { .. } => "UnexpectedSeek",
+            Self::UnexpectedAlign { .. } => "UnexpectedAlign",
+            Self::Context { .. } => "Context",
+        }
+    }
+}
+
+// keeps the error itself when it can be sent, so its kind can still be looked at later
+fn into_source(e: Box<dyn Error>) -> Box<dyn Error + Send + Sync> {
+    let e = match e.downcast::<FileParseError>() {
+        Ok(e) => return e,
+        Err(e) => e,
+    };
+    match e.downcast::<std::io::Error>() {
+        Ok(e) => e,
+        Err(e) => e.to_string().into(),
+    }
+}
 
 impl fmt::Display for FileParseError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -26,6 +68,65 @@ impl fmt::Display for FileParseError {
             Self::InvalidBool(v) => write!(f, "Invalid value {} for bool", *v),
             Self::BadAlign(pos, align) => write!(f, "Non-zero padding with pos:{:08X}, align:{:08X}", *pos, *align),
             Self::InvalidRszTypeHash(v) => write!(f, "Invalid type hash {:08X} not found in rsz", *v),
+            Self::OutOfBounds { offset, expected, len } => write!(f, "Tried to read {} bytes at {:#x} but the data is only {:#x} bytes long", expected, offset, len),
+            Self::UnexpectedSeek { pos, expected } => write!(f, "Expected to already be at 0x{:08X}, but at 0x{:08X}", expected, pos),
+            Self::UnexpectedAlign { pos, expected, align } => write!(f, "Expected 0x{:08X} to only be an align up {} from 0x{:08X}", expected, align, pos),
+            Self::Context { file, offset, field, source } => {
+                if let Some(file) = file {
+                    write!(f, "{}: ", file)?;
+                }
+                if let Some(offset) = offset {
+                    write!(f, "at 0x{:08X} ", offset)?;
+                }
+                if let Some(field) = field {
+                    write!(f, "in {} ", field)?;
+                }
+                write!(f, "{}", source)
+            },
         }
     }
 }
+
+// Adds file/offset/field info to an error. Nested calls build the field path outwards,
+// so the innermost offset is kept and an outer "ItemData.cData[12]" and inner "Price"
+// end up as "ItemData.cData[12].Price".
+pub trait Context<T> {
+    fn context(self, field: Option<String>, offset: Option<u64>) -> Result<T>;
+    fn file_context(self, file: &str) -> Result<T>;
+}
+
+impl<T, E: Into<Box<dyn Error>>> Context<T> for std::result::Result<T, E> {
+    fn context(self, field: Option<String>, offset: Option<u64>) -> Result<T> {
+        self.map_err(|e| {
+            let e: Box<dyn Error> = e.into();
+            match e.downcast::<FileParseError>() {
+                Ok(e) => match *e {
+                    FileParseError::Context { file, offset: inner_offset, field: inner_field, source } => {
+                        let field = match (field, inner_field) {
+                            (Some(outer), Some(inner)) if inner.starts_with('[') => Some(outer + &inner),
+                            (Some(outer), Some(inner)) => Some(format!("{outer}.{inner}")),
+                            (outer, inner) => inner.or(outer),
+                        };
+                        Box::new(FileParseError::Context { file, offset: inner_offset.or(offset), field, source }) as Box<dyn Error>
+                    },
+                    e => Box::new(FileParseError::Context { file: None, offset, field, source: Box::new(e) }),
+                },
+                Err(e) => Box::new(FileParseError::Context { file: None, offset, field, source: into_source(e) }),
+            }
+        })
+    }
+
+    fn file_context(self, file: &str) -> Result<T> {
+        self.context(None, None).map_err(|e| {
+            match e.downcast::<FileParseError>() {
+                Ok(e) => match *e {
+                    FileParseError::Context { offset, field, source, .. } => {
+                        Box::new(FileParseError::Context { file: Some(file.to_string()), offset, field, source }) as Box<dyn Error>
+                    },
+                    e => Box::new(e),
+                },
+                Err(e) => e,
+            }
+        })
+    }
+}
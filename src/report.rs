@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::reerr::{FileParseError, Result};
+
+#[derive(Debug, Serialize)]
+pub struct ErrorInfo {
+    pub kind: String,
+    pub message: String,
+    pub offset: Option<u64>,
+    pub field: Option<String>,
+    pub class_hash: Option<u32>,
+}
+
+impl ErrorInfo {
+    // digs through Context and io::Error wrappers for the error that actually happened
+    pub fn new(e: &(dyn Error + 'static)) -> ErrorInfo {
+        let mut info = ErrorInfo {
+            kind: "Other".to_string(),
+            message: e.to_string(),
+            offset: None,
+            field: None,
+            class_hash: None,
+        };
+        let mut cur: Option<&(dyn Error + 'static)> = Some(e);
+        while let Some(e) = cur {
+            if let Some(e) = e.downcast_ref::<FileParseError>() {
+                match e {
+                    FileParseError::Context { offset, field, .. } => {
+                        info.offset = info.offset.or(*offset);
+                        info.field = info.field.take().or(field.clone());
+                    },
+                    FileParseError::InvalidRszTypeHash(hash) => {
+                        info.class_hash = Some(*hash);
+                        info.kind = e.kind().to_string();
+                    },
+                    FileParseError::OutOfBounds { offset, .. } => {
+                        info.offset = info.offset.or(Some(*offset as u64));
+                        info.kind = e.kind().to_string();
+                    },
+                    FileParseError::UnexpectedSeek { pos, .. } | FileParseError::UnexpectedAlign { pos, .. }
+                        | FileParseError::BadAlign(pos, _) => {
+                        info.offset = info.offset.or(Some(*pos));
+                        info.kind = e.kind().to_string();
+                    },
+                    _ => info.kind = e.kind().to_string(),
+                }
+            } else if let Some(io) = e.downcast_ref::<std::io::Error>() {
+                if info.kind == "Other" {
+                    info.kind = format!("Io({:?})", io.kind());
+                }
+                // io errors hide what they wrap from source()
+                cur = io.get_ref().map(|e| e as &(dyn Error + 'static));
+                continue
+            }
+            cur = e.source();
+        }
+        info
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReportEntry {
+    pub input: PathBuf,
+    pub file_type: &'static str,
+    pub version: Option<u32>,
+    pub outputs: Vec<PathBuf>,
+    pub input_bytes: u64,
+    pub output_bytes: u64,
+    pub duration_ms: f64,
+    pub skipped: bool,
+    pub error: Option<ErrorInfo>,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct Summary {
+    files: usize,
+    ok: usize,
+    skipped: usize,
+    failed: usize,
+    errors_by_kind: HashMap<String, usize>,
+    errors_by_type: HashMap<&'static str, usize>,
+    top_missing_hashes: Vec<(String, usize)>,
+}
+
+// One json line per input, then a summary line when finished
+pub struct Report {
+    writer: Option<BufWriter<File>>,
+    summary: Summary,
+    missing_hashes: HashMap<u32, usize>,
+}
+
+impl Report {
+    pub fn new(path: Option<&Path>) -> Result<Report> {
+        let writer = match path {
+            Some(path) => Some(BufWriter::new(File::create(path)?)),
+            None => None,
+        };
+        Ok(Report {
+            writer,
+            summary: Summary::default(),
+            missing_hashes: HashMap::new(),
+        })
+    }
+
+    pub fn record(&mut self, entry: ReportEntry) -> Result<()> {
+        self.summary.files += 1;
+        match &entry.error {
+            Some(error) => {
+                self.summary.failed += 1;
+                *self.summary.errors_by_kind.entry(error.kind.clone()).or_default() += 1;
+                *self.summary.errors_by_type.entry(entry.file_type).or_default() += 1;
+                if let Some(hash) = error.class_hash {
+                    *self.missing_hashes.entry(hash).or_default() += 1;
+                }
+            },
+            None if entry.skipped => self.summary.skipped += 1,
+            None => self.summary.ok += 1,
+        }
+        if let Some(writer) = &mut self.writer {
+            serde_json::to_writer(&mut *writer, &entry)?;
+            writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        let mut hashes: Vec<_> = self.missing_hashes.into_iter().collect();
+        hashes.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        self.summary.top_missing_hashes = hashes.into_iter().take(20)
+            .map(|(hash, count)| (format!("{hash:08X}"), count))
+            .collect();
+
+        let summary = &self.summary;
+        eprintln!("[INFO] {} files, {} ok, {} unchanged, {} failed", summary.files, summary.ok, summary.skipped, summary.failed);
+        for (hash, count) in &summary.top_missing_hashes {
+            eprintln!("[INFO] missing rsz type {hash}: {count} files");
+        }
+        if let Some(writer) = &mut self.writer {
+            serde_json::to_writer(&mut *writer, &serde_json::json!({ "summary": summary }))?;
+            writer.write_all(b"\n")?;
+            writer.flush()?;
+        }
+        Ok(())
+    }
+}
@@ -9,10 +9,11 @@ use std::collections::HashSet;
 use std::convert::TryFrom;
 use std::fmt::Debug;
 use std::fs::File;
-use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
 use std::path::Path;
 use std::path::PathBuf;
 use crate::reerr::*;
+use crate::align::align_up;
 
 #[derive(Debug, Clone)]
 pub struct Extern {
@@ -32,6 +33,8 @@ pub struct Rsz {
     pub extern_slots: HashMap<u32, Extern>,
     pub type_descriptors: Vec<TypeDescriptor>,
     pub data: Vec<u8>,
+    // where data started in the file it was read from, for error offsets
+    pub data_offset: u64,
 }
 
 #[derive(Debug, Clone)]
@@ -85,7 +88,7 @@ impl Rsz {
             .collect::<Result<Vec<_>>>()?;
 
         if type_descriptors.first() != Some(&TypeDescriptor { hash: 0, crc: 0 }) {
-            return Err(format!("The first type descriptor should be 0").into())
+            return Err("The first type descriptor should be 0".into())
         }
 
         file.seek_assert_align_up(base + string_table_offset, 16)?;
@@ -105,14 +108,12 @@ impl Rsz {
                 file.seek_noop(base + offset)?;
                 let path = file.read_u16str()?;
                 if !path.ends_with(".user") {
-                    return Err(format!("Non-USER slot string").into());
+                    return Err("Non-USER slot string".into());
                 }
-                if hash != type_descriptors
-                        .get(usize::try_from(slot)?)
-                        .expect("slot out of bound")
-                        .hash
-                {
-                    return Err(format!("slot hash mismatch").into())
+                let slot_type = type_descriptors.get(usize::try_from(slot)?)
+                    .ok_or_else(|| format!("Extern slot {slot} out of bounds of {} type descriptors", type_descriptors.len()))?;
+                if hash != slot_type.hash {
+                    return Err("slot hash mismatch".into())
                 }
                 Ok((slot, Extern { hash, path }))
             })
@@ -120,10 +121,12 @@ impl Rsz {
         //println!("{extern_slots:?}");
         //println!("{}", base + data_offset);
         //file.seek(SeekFrom::Start(base + data_offset))?;
-        file.seek_assert_align_up(base + data_offset, 16)?;
+        let data_start = file.seek_assert_align_up(base + data_offset, 16)?;
         let mut data: Vec<u8> = vec![];
         if cap != 0 {
-            data = vec![0u8; cap as usize - file.tell()? as usize];
+            let len = cap.checked_sub(data_start)
+                .ok_or_else(|| format!("RSZ data at 0x{data_start:08X} starts past its end 0x{cap:08X}"))?;
+            data = vec![0u8; len as usize];
             file.read_exact(&mut data)?
         } else {
             file.read_to_end(&mut data)?;
@@ -133,10 +136,85 @@ impl Rsz {
             extern_slots,
             type_descriptors,
             data,
+            data_offset: data_start,
         })
     }
 
 
+    pub fn write<W: Write + Seek>(&self, file: &mut W) -> Result<()> {
+        let base = file.stream_position()?;
+        let mut slots: Vec<(&u32, &Extern)> = self.extern_slots.iter().collect();
+        slots.sort_by_key(|(slot, _)| **slot);
+
+        let type_descriptor_offset = 0x30 + self.roots.len() as u64 * 4;
+        let string_table_offset = align_up(type_descriptor_offset + self.type_descriptors.len() as u64 * 8, 16);
+        let mut string_offset = string_table_offset + slots.len() as u64 * 16;
+        let mut string_offsets = Vec::new();
+        for (_, ext) in &slots {
+            string_offsets.push(string_offset);
+            string_offset += (ext.path.encode_utf16().count() as u64 + 1) * 2;
+        }
+        let data_offset = align_up(string_offset, 16);
+
+        file.write_all(b"RSZ\0")?;
+        file.write_u32(0x10)?;
+        file.write_u32(self.roots.len() as u32)?;
+        file.write_u32(self.type_descriptors.len() as u32)?;
+        file.write_u32(slots.len() as u32)?;
+        file.write_u32(0)?;
+        file.write_u64(type_descriptor_offset)?;
+        file.write_u64(data_offset)?;
+        file.write_u64(string_table_offset)?;
+        for root in &self.roots {
+            file.write_u32(*root)?;
+        }
+        for td in &self.type_descriptors {
+            file.write_u32(td.hash)?;
+            file.write_u32(td.crc)?;
+        }
+        file.write_align_up(16)?;
+        for ((slot, ext), offset) in slots.iter().zip(&string_offsets) {
+            file.write_u32(**slot)?;
+            file.write_u32(ext.hash)?;
+            file.write_u64(*offset)?;
+        }
+        for (_, ext) in &slots {
+            file.write_u16str(&ext.path)?;
+        }
+        file.write_align_up(16)?;
+        if file.stream_position()? != base + data_offset {
+            return Err("RSZ data offset mismatch".into())
+        }
+        file.write_all(&self.data)?;
+        Ok(())
+    }
+
+    // Rebuilds the block from its parsed values and reads it back, any difference to the
+    // original is an error with the file offset of the first differing data byte
+    pub fn check_rebuild(&self) -> Result<()> {
+        let mut block = Cursor::new(Vec::new());
+        self.deserializev2(None)?.to_rsz()?.write(&mut block)?;
+        let rebuilt = Rsz::new(&mut block, 0, 0)?;
+        if rebuilt.roots != self.roots {
+            return Err(format!("Rebuilt roots {:?} differ from {:?}", rebuilt.roots, self.roots).into())
+        }
+        if rebuilt.type_descriptors != self.type_descriptors {
+            return Err("Rebuilt type descriptors differ".into())
+        }
+        for (slot, ext) in &self.extern_slots {
+            if rebuilt.extern_slots.get(slot).map(|e| &e.path) != Some(&ext.path) {
+                return Err(format!("Rebuilt extern slot {slot} differs from {}", ext.path).into())
+            }
+        }
+        if rebuilt.data != self.data {
+            let pos = rebuilt.data.iter().zip(&self.data).position(|(a, b)| a != b)
+                .unwrap_or(rebuilt.data.len().min(self.data.len()));
+            return Err(format!("Rebuilt RSZ data of {} bytes differs from the original {} bytes", rebuilt.data.len(), self.data.len()))
+                .context(None, Some(self.data_offset + pos as u64))
+        }
+        Ok(())
+    }
+
     pub fn deserializev2(&self, root_dir: Option<String>) -> Result<DeRsz> {
         //println!("{:?}", &self.data[0..128]);
         let mut cursor = Cursor::new(&self.data);
@@ -170,7 +248,7 @@ impl Rsz {
                 continue;
             } else {
                 // check for object index and return that too
-                let something = RszDump::parse_struct(&mut cursor, TypeDescriptor{hash, crc})?;
+                let something = RszDump::parse_struct(&mut cursor, TypeDescriptor{hash, crc}, Some(self.data_offset))?;
                 structs.push(something);
             }
         }
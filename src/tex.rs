@@ -1,43 +1,104 @@
 use libdeflater::TileStream;
 
 use crate::bitfield::BitField;
-use crate::reerr::FileParseError::{self, MagicError};
+use crate::reerr::{Context, FileParseError::{self, MagicError}};
 use crate::file_ext::*;
-use crate::compression::{
-    Bc1Unorm, Bc3Unorm, Bc4Unorm, Bc5Unorm, Bc7Unorm, CompressionType, R8G8B8A8Unorm, R8G8Unorm, R8Unorm, TexCodec
-};
+use crate::gdeflate;
+use crate::compression::{decode_threads, srgb_to_linear, RawCell, TexCodec, TexFormat, TexLayout};
+
+use image::imageops::FilterType;
+use serde::Serialize;
 
 use std::error::Error;
 use std::fmt;
 use std::str;
 use std::result::Result;
-use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
 
-pub struct RGBAImage {
-    pub data: Vec<u8>,
+// 8 bit or float texels, 4 per pixel
+pub struct RGBAImage<T = u8> {
+    pub data: Vec<T>,
     pub width: u32,
     pub height: u32,
 }
 
+// images with the name suffix they're saved with
+pub type NamedImages<T> = Vec<(String, RGBAImage<T>)>;
+
+// layout codes. 0xFFFFFFFF is the linear code the original decoder read pc textures with, 1 is
+// taken as block linear (the Tegra X1 tiling of Switch builds) and any other code is rejected
+// rather than guessed. Block linear keeps log2 of the block size in GOBs in the swizzle fields,
+// like the block height field of the Tegra texture descriptors, see block_linear_untiles in
+// compression.rs for the untiling checked against the yuzu/Ryujinx address formula.
+const LAYOUT_LINEAR: u32 = 0xFFFFFFFF;
+const LAYOUT_BLOCK_LINEAR: u32 = 1;
+
+// versions with the swizzle fields after the header
+fn has_swizzle_fields(version: u32) -> bool {
+    version > 27 && version != 190820018
+}
+
+// versions with a table of (possibly GDeflate compressed) sections after the tex infos
+fn has_gdef_sections(version: u32) -> bool {
+    version == 240701001 || version == 241106027
+}
+
 #[derive(Debug, Clone)]
 pub struct Tex {
+    version: u32,
     width: u32,
     height: u32,
     format: u32,
+    depth: u32,
     layout: u32,
+    cubemap: u32,
+    // (width, height) as log2 of the super block size in GOBs
+    swizzle: Option<(u8, u8)>,
     tex_infos: Vec<TexInfo>,
     textures: Vec<Vec<u8>>,
     _tex_count: usize,
     mip_count: usize,
 }
 
-#[derive(Debug, Clone)]
-struct TexInfo {
+#[derive(Debug, Clone, Serialize)]
+pub struct TexInfo {
     offset: u64,
     compressed_size : u32,
     len: u32,
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct GDefSection {
+    compressed_size: u32,
+    offset: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SwizzleFields {
+    swizzle_height_depth: u8,
+    swizzle_width: u8,
+}
+
+// Everything before the texture data, read without decoding anything
+#[derive(Debug, Clone, Serialize)]
+pub struct TexHeader {
+    pub version: u32,
+    pub width: u16,
+    pub height: u16,
+    pub depth: u16,
+    pub tex_count: u16,
+    pub mip_count: u16,
+    pub format: u32,
+    pub format_name: Option<&'static str>,
+    pub layout: u32,
+    pub cubemap: u32,
+    pub unkn1: u8,
+    pub unkn2: u8,
+    pub swizzle: Option<SwizzleFields>,
+    pub tex_infos: Vec<TexInfo>,
+    pub sections: Option<Vec<GDefSection>>,
+}
+
 impl fmt::Display for TexInfo {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -48,8 +109,9 @@ impl fmt::Display for TexInfo {
     }
 }
 
-impl Tex {
-    pub fn new<F: Read + Seek>(mut file: F) -> std::result::Result<Tex, Box<dyn Error>> {
+impl TexHeader {
+    // Reads the header, tex infos and section table, the file is left at the start of the data
+    pub fn read<F: Read + Seek>(file: &mut F) -> std::result::Result<TexHeader, Box<dyn Error>> {
         let magic = file.read_magic()?;
         let ext = str::from_utf8(&magic)?;
         if ext != "TEX\0" {
@@ -65,66 +127,43 @@ impl Tex {
         let depth = file.read_u16()?;
 
         let counts = file.read_u16()?;
-        let (tex_count, mipmap_count) = counts.bit_split((12, 4));
+        let (tex_count, mip_count) = counts.bit_split((12, 4));
 
         let format = file.read_u32()?;
         let layout = file.read_u32()?;
 
-        let _cubemap = file.read_u32()?;
-        let _unkn1 = file.read_u8()?; // These are some weird bit flags
-        let _unkn2 = file.read_u8()?;
+        let cubemap = file.read_u32()?;
+        let unkn1 = file.read_u8()?; // These are some weird bit flags
+        let unkn2 = file.read_u8()?;
         let _null1 = file.read_u16()?;
 
-        // no idea where this is used, it's just zero?
-        if version > 27 && version != 190820018 {
-            let _swizzle_height_depth = file.read_u8()?;
-            let _swizzle_width = file.read_u8()?;
+        // zero on pc, super block sizes for the block linear layout on console
+        let swizzle = if has_swizzle_fields(version) {
+            let swizzle_height_depth = file.read_u8()?;
+            let swizzle_width = file.read_u8()?;
             let _null2 = file.read_u16()?;
             let _seven = file.read_u16()?;
             let _one = file.read_u16()?;
-        }
-
-        /*println!("magic: {:?}", magic);
-        println!("version: {:?}", version);
-        println!("dims: {width}, {height}, {depth}");
-        println!("format: {format:#010x}, layout: {layout:#010x}");
-        println!("counts: {counts},texs: {tex_count}, mipmaps: {mipmap_count}");
-        println!("{_unkn}, {_unkn1:08b}, {_unkn2:08b}, {_null1}");*/
-
-        //println!("{_swizzle_height_depth:?}, {_swizzle_width:?}, {_null2:?}, {_seven:?}, {_one:?}");
-
-        let mut tex_infos = Vec::new();
+            Some(SwizzleFields { swizzle_height_depth, swizzle_width })
+        } else {
+            None
+        };
 
-        let mut decompressed_size = 0;
-        for _i in 0..tex_count {
-            for _j in 0..mipmap_count {
+        let tex_infos = (0..tex_count * mip_count)
+            .map(|_| {
                 let offset = file.read_u64()?;
                 let compressed_size = file.read_u32()?;
                 let len = file.read_u32()?;
-                decompressed_size += len * depth as u32;
-                tex_infos.push(TexInfo { offset, compressed_size, len });
-            }
-        }
-
-        //println!("{tex_infos:#?}");
-        #[derive(Debug)]
-        struct GDefSection {
-            compressed_size: u32,
-            offset: u32,
-        }
+                Ok(TexInfo { offset, compressed_size, len })
+            })
+            .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
 
-        let mut total_size = 0;
-        let gdef_sections = if version == 240701001 || version == 241106027{
-            let sections = (0..mipmap_count * tex_count)
-                .into_iter()
+        let sections = if has_gdef_sections(version) {
+            let sections = (0..tex_count * mip_count)
                 .map(|_| {
                     let compressed_size = file.read_u32()?;
                     let offset = file.read_u32()?;
-                    total_size += compressed_size;
-                    Ok(GDefSection {
-                        compressed_size,
-                        offset,
-                    })
+                    Ok(GDefSection { compressed_size, offset })
                 })
                 .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
             Some(sections)
@@ -132,30 +171,98 @@ impl Tex {
             None
         };
 
+        Ok(TexHeader {
+            version,
+            width,
+            height,
+            depth,
+            tex_count,
+            mip_count,
+            format,
+            format_name: TexFormat::from_code(format).map(|format| format.name),
+            layout,
+            cubemap,
+            unkn1,
+            unkn2,
+            swizzle,
+            tex_infos,
+            sections,
+        })
+    }
+
+    // bytes of texture data once decompressed, 3D mips hold every depth slice
+    pub fn data_size(&self) -> u64 {
+        self.tex_infos.iter().enumerate()
+            .map(|(i, info)| info.len as u64 * u16::max(self.depth >> (i % self.mip_count.max(1) as usize), 1) as u64)
+            .sum()
+    }
+
+    // bytes of texture data stored in the file
+    pub fn stored_size(&self) -> u64 {
+        match &self.sections {
+            Some(sections) => sections.iter().map(|section| section.compressed_size as u64).sum(),
+            None => self.data_size(),
+        }
+    }
+}
+
+impl fmt::Display for TexHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)?;
+        if self.depth > 1 {
+            write!(f, "x{}", self.depth)?;
+        }
+        match self.format_name {
+            Some(name) => write!(f, " {name}")?,
+            None => write!(f, " format {:02X}", self.format)?,
+        }
+        write!(f, ", {} mips, {} textures", self.mip_count, self.tex_count)?;
+        if self.cubemap != 0 {
+            write!(f, ", cubemap")?;
+        }
+        if self.layout != LAYOUT_LINEAR {
+            write!(f, ", layout {:08X}", self.layout)?;
+        }
+        write!(f, ", {} bytes ({} stored)", self.data_size(), self.stored_size())
+    }
+}
+
+impl Tex {
+    pub fn new<F: Read + Seek>(mut file: F) -> std::result::Result<Tex, Box<dyn Error>> {
+        let TexHeader { version, width, height, depth, tex_count, mip_count: mipmap_count, format, layout, cubemap, swizzle, tex_infos, sections: gdef_sections, .. }
+            = TexHeader::read(&mut file)?;
+        let swizzle = swizzle.map(|swizzle| (swizzle.swizzle_width, swizzle.swizzle_height_depth & 0xF));
+        let decompressed_size = tex_infos.iter().map(|info| info.len * depth as u32).sum::<u32>();
+
         //println!("{gdef_sections:?}");
-        let base = tex_infos[0].offset + mipmap_count as u64 * tex_count as u64 * 8;
+        let first = tex_infos.first().ok_or_else(|| FileParseError::TexReadError { source: String::from("No tex infos") })?;
+        let base = first.offset + mipmap_count as u64 * tex_count as u64 * 8;
         let mut bytes_read = 0;
         let textures = tex_infos
             .iter()
             .enumerate()
-            .map(|(i, tex_info)| {
+            .map(|(i, tex_info)| -> Result<Vec<u8>, Box<dyn Error>> {
+                // 3D mips hold every depth slice
+                let full_len = tex_info.len * u16::max(depth >> (i % mipmap_count as usize), 1) as u32;
                 let in_size = match &gdef_sections {
                     Some(sections) => {
                         let in_size = sections[i].compressed_size;
-                        file.seek_noop(base + sections[i].offset as u64).expect("Invalid file base");
+                        let offset = base + sections[i].offset as u64;
+                        file.seek_noop(offset).context(Some(format!("section {i}")), Some(offset))?;
                         in_size
                     },
-                    None => tex_info.len
+                    None => full_len
                 };
                 //println!("in_size {}, out_size {}", in_size, tex_info.len);
-                let in_buf = file.read_u8_n(in_size as usize).unwrap();
-                if tex_info.len == in_size {
+                let in_buf = file.read_u8_n(in_size as usize)?;
+                if tex_info.len == in_size || full_len == in_size {
                     bytes_read += in_size;
                     return Ok(in_buf);
                 }
 
                 let mut in_data = Cursor::new(&in_buf);
-                let _header = TileStream::from(&mut in_data).unwrap();
+                let _header = TileStream::from(&mut in_data)
+                    .map_err(|e| FileParseError::GDeflateError { source: format!("{e:?}") })?;
 
                 let out_size = u32::max(_header.get_uncompressed_size() as u32, tex_info.len * depth as u32);
                 let mut out_buf: Vec<u8> = Vec::new();
@@ -180,10 +287,14 @@ impl Tex {
         }
 
         let tex = Tex {
+            version,
             width: width as u32,
             height: height as u32,
+            depth: depth as u32,
             format,
             layout,
+            cubemap,
+            swizzle,
             tex_infos,
             textures,
             mip_count: mipmap_count as usize,
@@ -192,43 +303,118 @@ impl Tex {
         Ok(tex)
     }
 
-    pub fn to_rgba(&self, tex_idx: usize, mip_idx: usize) -> Result<RGBAImage, Box<dyn Error>> {
-        let texture = &self.textures[tex_idx * self.mip_count + mip_idx];
-        let tex_info = &self.tex_infos[tex_idx * self.mip_count + mip_idx];
-        //println!("{tex_info:?}");
-        let swizzle = "rgba";
+    fn tex_layout(&self) -> Result<TexLayout, Box<dyn Error>> {
+        match (self.layout, self.swizzle) {
+            (LAYOUT_LINEAR, _) => Ok(TexLayout::Linear),
+            // pc files leave the swizzle fields zero
+            (LAYOUT_BLOCK_LINEAR, None | Some((0, 0))) => Ok(TexLayout::Linear),
+            (LAYOUT_BLOCK_LINEAR, Some((width, height))) => Ok(TexLayout::BlockLinear {
+                super_width: 1 << width,
+                super_height: 1 << height,
+            }),
+            (layout, _) => Err(Box::new(FileParseError::Unsupported {
+                source: format!("unknown texture layout {layout:08X}")
+            })),
+        }
+    }
+
+    pub fn mip_count(&self) -> usize {
+        self.mip_count
+    }
+
+    pub fn tex_count(&self) -> usize {
+        self._tex_count
+    }
+
+    pub fn is_cubemap(&self) -> bool {
+        self.cubemap != 0 && self._tex_count.is_multiple_of(6)
+    }
 
-        let bpps = CompressionType::get_bpps(self.format);
-        if bpps == 0 {
-                return Err(Box::new(FileParseError::Unsupported { source: format!("unsupported format {:02X}", self.format) }))
+    pub fn mip_size(&self, mip_idx: usize) -> (u32, u32, u32) {
+        let shrink = |x: u32| u32::max(x >> mip_idx, 1);
+        (shrink(self.width), shrink(self.height), shrink(self.depth))
+    }
+
+    // depth slices actually stored for a mip, a 3D mip is its slices one after another
+    pub fn slice_count(&self, tex_idx: usize, mip_idx: usize) -> usize {
+        let idx = tex_idx * self.mip_count + mip_idx;
+        let len = self.tex_infos[idx].len as usize;
+        if len == 0 {
+            return 0
         }
-        let texel_size = if bpps == 4 || bpps == 8 {
-            4
-        } else {
-            bpps / 8
+        usize::min(self.mip_size(mip_idx).2 as usize, self.textures[idx].len() / len).max(1)
+    }
+
+    // One depth slice of a mip with the row padding stripped and any tiling undone,
+    // rows of cells packed one after another
+    pub fn linear_slice(&self, tex_idx: usize, mip_idx: usize, slice_idx: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+        let layout = self.tex_layout()?;
+        let idx = tex_idx * self.mip_count + mip_idx;
+        let (Some(texture), Some(tex_info)) = (self.textures.get(idx), self.tex_infos.get(idx)) else {
+            return Err(Box::new(FileParseError::TexReadError {
+                source: format!("no texture {tex_idx} mip {mip_idx}, file has {} textures with {} mips", self._tex_count, self.mip_count)
+            }))
         };
-        let bit_amount = self.width * bpps;
-        //println!("{bit_amount}");
-        let pad = 8 - if bpps < 8 { bit_amount } else { 0 }; 
-        //println!("{pad}");
-        let read_len = (bit_amount / 8 + pad) * texel_size - pad * 4;
-        let (width, height) = (self.width as u32, self.height as u32);
-        //println!("w{}, h{}, pad:{pad}", width, height);
-        //println!("readlen: {read_len}");
-
-        let mut data2 = vec![];//0; (self.width as usize) * (self.height as usize) * 4 ];
-        let mut t = Cursor::new(&texture);
-        for _i in 0..tex_info.len / tex_info.compressed_size {
-            let x = t.read_u8_n(read_len as usize)?;
-            t.seek(SeekFrom::Current((tex_info.compressed_size as u32 - read_len as u32) as i64))?;
-            data2.extend(x);
+        let slice_len = tex_info.len as usize;
+        let texture = texture.get(slice_idx * slice_len..).unwrap_or_default();
+        let texture = &texture[..usize::min(slice_len, texture.len())];
+
+        let (width, height, _) = self.mip_size(mip_idx);
+        let format = self.tex_format()?;
+        let read_len = format.row_len(width as usize);
+        let cell_size = format.kind.cell().0;
+        let rows = (height as usize).div_ceil(cell_size);
+
+        match layout {
+            // rows are padded out to the pitch
+            TexLayout::Linear => {
+                let pitch = usize::max(tex_info.compressed_size as usize, read_len);
+                let mut data2 = vec![];
+                let mut t = Cursor::new(&texture);
+                for _i in 0..usize::min(rows, texture.len() / pitch) {
+                    let x = t.read_u8_n(read_len)?;
+                    t.seek(SeekFrom::Current((pitch - read_len) as i64))?;
+                    data2.extend(x);
+                }
+                Ok(data2)
+            },
+            TexLayout::BlockLinear { .. } => {
+                let cells = (width as usize).div_ceil(cell_size);
+                let cell_len = read_len / cells;
+                let mut data = vec![0; read_len * rows];
+                macro_rules! untile {
+                    ($n:literal) => {
+                        RawCell::<$n>::decode_image(texture, cells, rows, layout, |x, y, cell| {
+                            data[(y * cells + x) * $n..][..$n].copy_from_slice(&cell)
+                        })
+                    };
+                }
+                match cell_len {
+                    1 => untile!(1),
+                    2 => untile!(2),
+                    4 => untile!(4),
+                    8 => untile!(8),
+                    16 => untile!(16),
+                    _ => return Err(Box::new(FileParseError::Unsupported { source: format!("can't untile {cell_len} byte cells") })),
+                }
+                Ok(data)
+            },
+        }
+    }
+
+    // swizzle is 4 of r/g/b/a (or x/y/z/w), 0, 1 and n for a normal Z rebuilt from the other color channels
+    pub fn to_rgba(&self, tex_idx: usize, mip_idx: usize, slice_idx: usize, swizzle: &str) -> Result<RGBAImage, Box<dyn Error>> {
+        let texture = self.linear_slice(tex_idx, mip_idx, slice_idx)?;
+        let (width, height, _) = self.mip_size(mip_idx);
+        //println!("w{}, h{}", width, height);
+
+        let texels = self.tex_format()?.kind.decode_linear::<[u8; 4]>(&texture, width as usize, height as usize, decode_threads());
+        if swizzle == "rgba" {
+            return Ok(RGBAImage { data: texels.concat(), width, height });
         }
-        let texture = data2;
 
         let mut data = vec![0; (width * height * 4) as usize];
-        let writer = |x: usize, y: usize, v: [u8; 4]| {
-            let i = (x + y * (width as usize)) * 4;
-            let dest = &mut data[i..][..4];
+        for (dest, v) in data.chunks_exact_mut(4).zip(texels) {
             for (dest, &code) in dest.iter_mut().zip(swizzle.as_bytes()) {
                 *dest = match code {
                     b'r' | b'x' => v[0],
@@ -243,9 +429,11 @@ impl Tex {
             }
 
             if let Some(n) = swizzle.as_bytes().iter().position(|&c| c == b'n') {
-                let mut l: f32 = dest
+                let mut l: f32 = dest[..3]
                     .iter()
-                    .map(|&x| {
+                    .enumerate()
+                    .filter(|&(i, _)| i != n)
+                    .map(|(_, &x)| {
                         let x = x as f32 / 255.0 * 2.0 - 1.0;
                         x * x
                     })
@@ -256,28 +444,504 @@ impl Tex {
                 let z = (((1.0 - l).sqrt() + 1.0) / 2.0 * 255.0).round() as u8;
                 dest[n] = z;
             }
-        };
-
-        // need to add 0xA and 0x5F
-        match self.format {
-            0x1C | 0x1D => R8G8B8A8Unorm::decode_image( &texture, width as usize, height as usize, self.layout, writer,),
-            0x31 => R8G8Unorm::decode_image( &texture, width as usize, height as usize, self.layout, writer,),
-            0x3D => R8Unorm::decode_image( &texture, width as usize, height as usize, self.layout, writer,),
-            0x47 | 0x48 => Bc1Unorm::decode_image( &texture, width as usize, height as usize, self.layout, writer,),
-            0x4D | 0x4E => Bc3Unorm::decode_image( &texture, width as usize, height as usize, self.layout, writer,),
-            0x50 => Bc4Unorm::decode_image( &texture, width as usize, height as usize, self.layout, writer,),
-            0x53 => Bc5Unorm::decode_image( &texture, width as usize, height as usize, self.layout, writer,),
-            0x62 | 0x63  => Bc7Unorm::decode_image( &texture, width as usize, height as usize, self.layout, writer,),
-            x => {
-                eprintln!("unsupported format {:08X}", x);
-                return Err(Box::new(FileParseError::Unsupported { source: format!("unsupported format {x:08X}") }))
-            }
-        };
+        }
 
         Ok(RGBAImage {
             data,
-            width: width as u32,
-            height: height as u32,
+            width,
+            height,
+        })
+    }
+
+    pub fn tex_format(&self) -> Result<&'static TexFormat, Box<dyn Error>> {
+        TexFormat::from_code(self.format).ok_or_else(|| {
+            FileParseError::Unsupported { source: format!("unsupported format {:02X}", self.format) }.into()
+        })
+    }
+
+    pub fn is_hdr(&self) -> bool {
+        TexFormat::from_code(self.format).is_some_and(|format| format.kind.is_float())
+    }
+
+    // linear float texels, srgb formats get converted
+    pub fn to_rgba_f32(&self, tex_idx: usize, mip_idx: usize, slice_idx: usize) -> Result<RGBAImage<f32>, Box<dyn Error>> {
+        let format = self.tex_format()?;
+        let texture = self.linear_slice(tex_idx, mip_idx, slice_idx)?;
+        let (width, height, _) = self.mip_size(mip_idx);
+        let mut texels = format.kind.decode_linear::<[f32; 4]>(&texture, width as usize, height as usize, decode_threads());
+        if format.srgb {
+            for c in texels.iter_mut().flat_map(|v| &mut v[..3]) {
+                *c = srgb_to_linear(*c);
+            }
+        }
+        Ok(RGBAImage { data: texels.concat(), width, height })
+    }
+
+    // New linear texture from mip chains, one per array slice or cubemap face, largest mip first
+    pub fn from_images(version: u32, format: &'static TexFormat, images: &[Vec<RGBAImage>]) -> Result<Tex, Box<dyn Error>> {
+        let Some(first) = images.first().and_then(|mips| mips.first()) else {
+            return Err(Box::new(FileParseError::TexReadError { source: String::from("no images to make a texture from") }))
+        };
+        let mip_count = images[0].len();
+        if images.len() > 0xFFF || mip_count > 0xF || images.iter().any(|mips| mips.len() != mip_count) {
+            return Err(Box::new(FileParseError::TexReadError {
+                source: format!("{} textures with {mip_count} mips can't go in a tex, at most 4095 with 15 mips each", images.len())
+            }))
+        }
+        let mut tex_infos = vec![];
+        let mut textures = vec![];
+        for image in images.iter().flatten() {
+            let Some(data) = format.kind.encode_image(&image.data, image.width as usize, image.height as usize) else {
+                return Err(Box::new(FileParseError::Unsupported { source: format!("no encoder for {}", format.name) }))
+            };
+            let pitch = format.row_len(image.width as usize) as u32;
+            tex_infos.push(TexInfo { offset: 0, compressed_size: pitch, len: data.len() as u32 });
+            textures.push(data);
+        }
+        Ok(Tex {
+            version,
+            width: first.width,
+            height: first.height,
+            depth: 1,
+            format: format.code,
+            layout: LAYOUT_LINEAR,
+            cubemap: 0,
+            swizzle: has_swizzle_fields(version).then_some((0, 0)),
+            tex_infos,
+            textures,
+            _tex_count: images.len(),
+            mip_count,
         })
     }
+
+    pub fn set_cubemap(&mut self) -> Result<(), Box<dyn Error>> {
+        if !self._tex_count.is_multiple_of(6) || self.width != self.height {
+            return Err(Box::new(FileParseError::TexReadError {
+                source: format!("a cubemap needs square faces in sets of 6, got {} {}x{}", self._tex_count, self.width, self.height)
+            }))
+        }
+        self.cubemap = 1;
+        Ok(())
+    }
+
+    // Writes what Tex::new reads
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), Box<dyn Error>> {
+        let mut header = vec![];
+        header.extend(b"TEX\0");
+        header.extend(self.version.to_le_bytes());
+        header.extend((self.width as u16).to_le_bytes());
+        header.extend((self.height as u16).to_le_bytes());
+        header.extend((self.depth as u16).to_le_bytes());
+        header.extend((self._tex_count as u16 | (self.mip_count as u16) << 12).to_le_bytes());
+        header.extend(self.format.to_le_bytes());
+        header.extend(self.layout.to_le_bytes());
+        header.extend(self.cubemap.to_le_bytes());
+        header.extend([0u8; 4]);
+        if let Some((width, height)) = self.swizzle.or(has_swizzle_fields(self.version).then_some((0, 0))) {
+            header.extend([height, width]);
+            header.extend(0u16.to_le_bytes());
+            header.extend(7u16.to_le_bytes());
+            header.extend(1u16.to_le_bytes());
+        }
+
+        let count = self.textures.len() as u64;
+        let table_start = header.len() as u64 + count * 16;
+        // with sections the data is GDeflate compressed where that's smaller, the first tex info
+        // points at the section table and section offsets are from the end of it
+        let sections = if has_gdef_sections(self.version) {
+            self.textures.iter().map(|texture| gdeflate_section(texture)).collect()
+        } else {
+            self.textures.clone()
+        };
+        let mut data_offset = table_start;
+        for (info, texture) in self.tex_infos.iter().zip(&self.textures) {
+            header.extend(data_offset.to_le_bytes());
+            header.extend(info.compressed_size.to_le_bytes());
+            header.extend(info.len.to_le_bytes());
+            data_offset += texture.len() as u64;
+        }
+        if has_gdef_sections(self.version) {
+            let mut section_offset = 0u32;
+            for section in &sections {
+                header.extend((section.len() as u32).to_le_bytes());
+                header.extend(section_offset.to_le_bytes());
+                section_offset += section.len() as u32;
+            }
+        }
+        writer.write_all(&header)?;
+        for section in &sections {
+            writer.write_all(section)?;
+        }
+        Ok(())
+    }
+
+    // Every image that should be written for the options, with a name suffix for each
+    pub fn export_images<T: Copy + Default>(
+        &self,
+        options: &TexExport,
+        decode: impl Fn(usize, usize, usize) -> Result<RGBAImage<T>, Box<dyn Error>>,
+    ) -> Result<NamedImages<T>, Box<dyn Error>> {
+        let mips = if options.all_mips { self.mip_count.max(1) } else { 1 };
+        let cube = self.is_cubemap() && (options.all_slices || options.cubemap != CubemapLayout::Faces);
+        let texs = if cube || options.all_slices { self._tex_count.max(1) } else { 1 };
+
+        let mut images = vec![];
+        for mip_idx in 0..mips {
+            let mip_name = if mip_idx > 0 { format!(".mip{mip_idx}") } else { String::new() };
+            let mut faces = vec![];
+            for tex_idx in 0..texs {
+                let slices = if options.all_slices { self.slice_count(tex_idx, mip_idx) } else { 1 };
+                for slice_idx in 0..slices {
+                    let image = decode(tex_idx, mip_idx, slice_idx)?;
+                    let mut name = String::new();
+                    if cube {
+                        if self._tex_count > 6 {
+                            name += &format!(".arr{}", tex_idx / 6);
+                        }
+                        name += &format!(".{}", CUBE_FACES[tex_idx % 6]);
+                    } else if texs > 1 {
+                        name += &format!(".arr{tex_idx}");
+                    }
+                    if slices > 1 {
+                        name += &format!(".z{slice_idx}");
+                    }
+                    name += &mip_name;
+
+                    if cube && options.cubemap != CubemapLayout::Faces {
+                        faces.push(image);
+                        if faces.len() == 6 {
+                            let image = match options.cubemap {
+                                CubemapLayout::Cross => cube_cross(&faces),
+                                _ => cube_equirect(&faces),
+                            };
+                            let array = if self._tex_count > 6 { format!(".arr{}", tex_idx / 6) } else { String::new() };
+                            images.push((format!("{array}.{}{mip_name}", options.cubemap.name()), image));
+                            faces.clear();
+                        }
+                    } else {
+                        images.push((name, image));
+                    }
+                }
+            }
+        }
+        Ok(images)
+    }
+}
+
+// dds face order
+const CUBE_FACES: [&str; 6] = ["px", "nx", "py", "ny", "pz", "nz"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CubemapLayout {
+    Faces,
+    Cross,
+    Equirect,
+}
+
+impl CubemapLayout {
+    pub fn from_name(name: &str) -> Result<CubemapLayout, String> {
+        match name.to_lowercase().as_str() {
+            "faces" => Ok(CubemapLayout::Faces),
+            "cross" => Ok(CubemapLayout::Cross),
+            "equirect" => Ok(CubemapLayout::Equirect),
+            _ => Err(format!("Unknown cubemap layout {name}, expected faces, cross or equirect")),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CubemapLayout::Faces => "faces",
+            CubemapLayout::Cross => "cross",
+            CubemapLayout::Equirect => "equirect",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TexExport {
+    pub all_mips: bool,
+    // array slices, cubemap faces and 3D depth slices
+    pub all_slices: bool,
+    pub cubemap: CubemapLayout,
+    pub dds: bool,
+    pub hdr_format: HdrFormat,
+    pub swizzle: SwizzleRules,
+    // write each channel as its own grayscale image
+    pub split_channels: bool,
+}
+
+// What float textures are written as, png clamps them to 0-1
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HdrFormat {
+    Png,
+    Exr,
+    Hdr,
+}
+
+impl HdrFormat {
+    pub fn from_name(name: &str) -> Result<HdrFormat, String> {
+        match name.to_lowercase().as_str() {
+            "png" => Ok(HdrFormat::Png),
+            "exr" => Ok(HdrFormat::Exr),
+            "hdr" => Ok(HdrFormat::Hdr),
+            _ => Err(format!("Unknown hdr format {name}, expected png, exr or hdr")),
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            HdrFormat::Png => "png",
+            HdrFormat::Exr => "exr",
+            HdrFormat::Hdr => "hdr",
+        }
+    }
+}
+
+// Swizzles picked by format name (BC5) or a tag in the file name (_NRM), the first matching rule
+// wins and textures no rule matches use the default
+#[derive(Debug, Clone)]
+pub struct SwizzleRules {
+    default: String,
+    rules: Vec<(String, String)>,
+}
+
+// used for `auto`, two channel normal maps get their Z back
+const AUTO_SWIZZLE: [(&str, &str); 2] = [("_NRM", "rgn1"), ("BC5", "rgn1")];
+
+impl SwizzleRules {
+    // comma separated, either a swizzle for the default or key=swizzle, e.g. `auto,_ALBD=rgb1`
+    pub fn from_spec(spec: &str) -> Result<SwizzleRules, String> {
+        let mut swizzle = SwizzleRules::default();
+        for item in spec.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+            match item.split_once('=') {
+                Some((key, value)) => {
+                    check_swizzle(value)?;
+                    if !key.starts_with('_') && TexFormat::from_name(key).is_none() {
+                        return Err(format!("Unknown swizzle key {key}, expected a format name or a _TAG from file names"));
+                    }
+                    swizzle.rules.push((key.to_string(), value.to_lowercase()));
+                }
+                None if item.eq_ignore_ascii_case("auto") => {
+                    swizzle.rules.extend(AUTO_SWIZZLE.iter().map(|&(key, value)| (key.to_string(), value.to_string())));
+                }
+                None => {
+                    check_swizzle(item)?;
+                    swizzle.default = item.to_lowercase();
+                }
+            }
+        }
+        Ok(swizzle)
+    }
+
+    pub fn for_texture(&self, file_name: &str, format: &TexFormat) -> &str {
+        let file_name = file_name.to_uppercase();
+        self.rules.iter()
+            .find(|(key, _)| match key.starts_with('_') {
+                true => file_name.contains(&key.to_uppercase()),
+                false => TexFormat::from_name(key).is_some_and(|key| key.kind == format.kind),
+            })
+            .map_or(&self.default, |(_, swizzle)| swizzle)
+    }
+}
+
+impl Default for SwizzleRules {
+    fn default() -> Self {
+        SwizzleRules { default: String::from("rgba"), rules: vec![] }
+    }
+}
+
+fn check_swizzle(swizzle: &str) -> Result<(), String> {
+    let swizzle = swizzle.to_lowercase();
+    if swizzle.len() != 4 || !swizzle.chars().all(|c| "rgbaxyzw01n".contains(c)) || swizzle.matches('n').count() > 1 {
+        return Err(format!("Invalid swizzle {swizzle}, expected 4 of r, g, b, a, x, y, z, w, 0, 1 and at most one n"));
+    }
+    Ok(())
+}
+
+// Grayscale image of each channel that isn't a constant 0 or 1, with its r/g/b/a name
+pub fn split_channels(image: &RGBAImage, swizzle: &str) -> Vec<(char, Vec<u8>)> {
+    "rgba".chars()
+        .zip(swizzle.bytes())
+        .enumerate()
+        .filter(|&(_, (_, code))| code != b'0' && code != b'1')
+        .map(|(i, (name, _))| (name, image.data.chunks(4).map(|p| p[i]).collect()))
+        .collect()
+}
+
+impl Default for TexExport {
+    fn default() -> Self {
+        TexExport {
+            all_mips: false,
+            all_slices: false,
+            cubemap: CubemapLayout::Faces,
+            dds: false,
+            hdr_format: HdrFormat::Exr,
+            swizzle: SwizzleRules::default(),
+            split_channels: false,
+        }
+    }
+}
+
+// Horizontal cross, 4 faces wide and 3 high
+//      +y
+//  -x  +z  +x  -z
+//      -y
+fn cube_cross<T: Copy + Default>(faces: &[RGBAImage<T>]) -> RGBAImage<T> {
+    let size = faces[0].width as usize;
+    let (width, height) = (size * 4, size * 3);
+    let mut data = vec![T::default(); width * height * 4];
+    let cells = [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (3, 1)];
+    for (face, (cx, cy)) in faces.iter().zip(cells) {
+        let row = usize::min(face.width as usize, size) * 4;
+        for y in 0..usize::min(face.height as usize, size) {
+            let src = &face.data[y * face.width as usize * 4..][..row];
+            let dest = ((cy * size + y) * width + cx * size) * 4;
+            data[dest..dest + row].copy_from_slice(src);
+        }
+    }
+    RGBAImage { data, width: width as u32, height: height as u32 }
+}
+
+// Latitude/longitude projection twice as wide as high, nearest sampled
+fn cube_equirect<T: Copy + Default>(faces: &[RGBAImage<T>]) -> RGBAImage<T> {
+    use std::f32::consts::PI;
+    let size = faces[0].width as usize;
+    let (width, height) = (size * 4, size * 2);
+    let mut data = vec![T::default(); width * height * 4];
+    for y in 0..height {
+        let lat = PI / 2.0 - (y as f32 + 0.5) / height as f32 * PI;
+        for x in 0..width {
+            let lon = (x as f32 + 0.5) / width as f32 * 2.0 * PI - PI;
+            let (dx, dy, dz) = (lat.cos() * lon.sin(), lat.sin(), lat.cos() * lon.cos());
+            let (ax, ay, az) = (dx.abs(), dy.abs(), dz.abs());
+            // face, major axis, s and t direction as in d3d cube sampling
+            let (face, ma, sc, tc) = if ax >= ay && ax >= az {
+                if dx > 0.0 { (0, ax, -dz, -dy) } else { (1, ax, dz, -dy) }
+            } else if ay >= az {
+                if dy > 0.0 { (2, ay, dx, dz) } else { (3, ay, dx, -dz) }
+            } else if dz > 0.0 {
+                (4, az, dx, -dy)
+            } else {
+                (5, az, -dx, -dy)
+            };
+            let face = &faces[face];
+            let (fw, fh) = (face.width as usize, face.height as usize);
+            let s = (((sc / ma + 1.0) / 2.0 * fw as f32) as usize).min(fw - 1);
+            let t = (((tc / ma + 1.0) / 2.0 * fh as f32) as usize).min(fh - 1);
+            let src = (t * fw + s) * 4;
+            let dest = (y * width + x) * 4;
+            data[dest..dest + 4].copy_from_slice(&face.data[src..src + 4]);
+        }
+    }
+    RGBAImage { data, width: width as u32, height: height as u32 }
+}
+
+// Compresses a section and checks it decompresses back, keeping it uncompressed otherwise
+fn gdeflate_section(data: &[u8]) -> Vec<u8> {
+    let compressed = gdeflate::compress(data);
+    if compressed.len() >= data.len() {
+        return data.to_vec();
+    }
+    let mut out_buf = vec![0; data.len()];
+    match libdeflater::GDeflateDecompressor::gdeflate_decompress(&compressed, &mut out_buf) {
+        Ok(_) if out_buf == data => compressed,
+        result => {
+            let reason = result.map_or_else(|e| e.to_string(), |_| String::from("different data"));
+            eprintln!("[WARNING] GDeflate section didn't decompress ({reason}), storing it uncompressed");
+            data.to_vec()
+        }
+    }
+}
+
+pub fn filter_from_name(name: &str) -> Result<FilterType, String> {
+    match name.to_lowercase().as_str() {
+        "nearest" => Ok(FilterType::Nearest),
+        "triangle" | "box" => Ok(FilterType::Triangle),
+        "catmullrom" => Ok(FilterType::CatmullRom),
+        "gaussian" => Ok(FilterType::Gaussian),
+        "lanczos3" => Ok(FilterType::Lanczos3),
+        _ => Err(format!("Unknown mip filter {name}, expected nearest, triangle, catmullrom, gaussian or lanczos3")),
+    }
+}
+
+// The image and count - 1 halvings of it, the full chain down to 1x1 if count is 0
+pub fn generate_mips(image: RGBAImage, count: usize, filter: FilterType) -> Vec<RGBAImage> {
+    let full = (32 - u32::max(image.width, image.height).leading_zeros()) as usize;
+    let count = if count == 0 { full } else { usize::min(count, full) };
+    let Some(base) = image::RgbaImage::from_raw(image.width, image.height, image.data.clone()) else {
+        return vec![image]
+    };
+    let mut mips = vec![image];
+    for mip in 1..count {
+        let (width, height) = (u32::max(base.width() >> mip, 1), u32::max(base.height() >> mip, 1));
+        let resized = image::imageops::resize(&base, width, height, filter);
+        mips.push(RGBAImage { data: resized.into_raw(), width, height });
+    }
+    mips
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(width: u32, height: u32, seed: u32) -> RGBAImage {
+        let data = (0..width * height * 4).map(|i| (i.wrapping_mul(2654435761) >> 11 ^ seed) as u8).collect();
+        RGBAImage { data, width, height }
+    }
+
+    // 28 is the first version with the swizzle fields, 241106027 adds the GDeflate sections
+    #[test]
+    fn from_images_write_read() {
+        let format = TexFormat::from_name("R8G8B8A8_UNORM").unwrap();
+        for version in [28, 241106027] {
+            let mips = generate_mips(image(20, 12, 0), 3, FilterType::Triangle);
+            let tex = Tex::from_images(version, format, std::slice::from_ref(&mips)).unwrap();
+            let mut file = Vec::new();
+            tex.write(&mut file).unwrap();
+
+            let header = TexHeader::read(&mut Cursor::new(&file)).unwrap();
+            assert_eq!((header.version, header.width, header.height, header.depth), (version, 20, 12, 1));
+            assert_eq!((header.tex_count, header.mip_count), (1, 3));
+            assert_eq!((header.format, header.layout, header.cubemap), (format.code, LAYOUT_LINEAR, 0));
+            assert_eq!(header.sections.is_some(), has_gdef_sections(version));
+
+            let read = Tex::new(Cursor::new(&file)).unwrap();
+            assert!(!read.is_cubemap());
+            for (i, mip) in mips.iter().enumerate() {
+                let texels = read.to_rgba(0, i, 0, "rgba").unwrap();
+                assert_eq!((texels.width, texels.height), (mip.width, mip.height), "version {version} mip {i}");
+                assert!(texels.data == mip.data, "version {version} mip {i}");
+            }
+        }
+    }
+
+    #[test]
+    fn cubemap_write_read() {
+        let format = TexFormat::from_name("BC1_UNORM").unwrap();
+        let faces: Vec<Vec<RGBAImage>> = (0..6).map(|face| generate_mips(image(16, 16, face), 2, FilterType::Triangle)).collect();
+        let mut tex = Tex::from_images(241106027, format, &faces).unwrap();
+        tex.set_cubemap().unwrap();
+        let mut file = Vec::new();
+        tex.write(&mut file).unwrap();
+
+        let header = TexHeader::read(&mut Cursor::new(&file)).unwrap();
+        assert_eq!((header.width, header.height, header.tex_count, header.mip_count), (16, 16, 6, 2));
+        assert_eq!(header.format, format.code);
+        assert_ne!(header.cubemap, 0);
+        let read = Tex::new(Cursor::new(&file)).unwrap();
+        assert!(read.is_cubemap());
+        assert_eq!(read.mip_size(1), (8, 8, 1));
+
+        let mut faces = faces;
+        faces.pop();
+        assert!(Tex::from_images(241106027, format, &faces).unwrap().set_cubemap().is_err());
+    }
+
+    #[test]
+    fn gdeflate_section_compresses() {
+        let data = image(64, 64, 7).data;
+        let section = gdeflate_section(&data);
+        assert!(section.len() < data.len());
+        assert_eq!(section, gdeflate::compress(&data));
+    }
 }
@@ -0,0 +1,550 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::msg::Msg;
+use crate::reerr::Result;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Po,
+    Xliff12,
+    Xliff20,
+    Csv,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Result<Format> {
+        Ok(match name {
+            "po" => Format::Po,
+            "xliff" | "xliff12" | "xlf" => Format::Xliff12,
+            "xliff2" | "xliff20" => Format::Xliff20,
+            "csv" => Format::Csv,
+            _ => return Err(format!("Unknown translation format {name}, expected po, xliff, xliff2 or csv").into()),
+        })
+    }
+
+    // xliff 1.2 and 2.0 share extensions so the version gets sniffed from the document
+    pub fn from_path(path: &Path, data: &str) -> Result<Format> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("po") | Some("pot") => Ok(Format::Po),
+            Some("csv") => Ok(Format::Csv),
+            Some("xlf") | Some("xliff") => {
+                if data.contains("urn:oasis:names:tc:xliff:document:2.0") {
+                    Ok(Format::Xliff20)
+                } else {
+                    Ok(Format::Xliff12)
+                }
+            },
+            _ => Err(format!("Could not tell translation format of {path:?}").into()),
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Po => "po",
+            Format::Xliff12 | Format::Xliff20 => "xlf",
+            Format::Csv => "csv",
+        }
+    }
+}
+
+// BCP 47 tags in the same order as Msg::lang_map
+const LANG_CODES: [&str; 34] = [
+    "ja", "en", "fr", "it", "de", "es", "ru", "pl", "nl", "pt", "pt-BR", "ko", "zh-TW", "zh-CN",
+    "fi", "sv", "da", "no", "cs", "hu", "sk", "ar", "tr", "bg", "el", "ro", "th", "uk", "vi", "id",
+    "x-fiction", "hi", "es-419", "und",
+];
+
+// ids past the table become lang<id>, "und" is already language 33
+pub fn lang_code(lang: usize) -> Cow<'static, str> {
+    match LANG_CODES.get(lang) {
+        Some(code) => Cow::Borrowed(code),
+        None => Cow::Owned(format!("lang{lang}")),
+    }
+}
+
+// accepts either a code (en, pt-BR) or a lang_map name (English, PortugueseBr)
+pub fn lang_from_str(lang: &str) -> Option<usize> {
+    LANG_CODES.iter().position(|code| code.eq_ignore_ascii_case(lang))
+        .or_else(|| Msg::lang_index(lang))
+}
+
+pub fn lang_name(lang: usize) -> Cow<'static, str> {
+    Msg::lang_name(lang)
+}
+
+pub fn export(msg: &Msg, format: Format, source: usize, target: usize, original: &str) -> String {
+    match format {
+        Format::Po => export_po(msg, source, target),
+        Format::Xliff12 => export_xliff12(msg, source, target, original),
+        Format::Xliff20 => export_xliff20(msg, source, target, original),
+        Format::Csv => export_csv(msg),
+    }
+}
+
+fn text<'a>(content: &[(usize, &'a str)], lang: usize) -> &'a str {
+    content.iter().find(|(l, _)| *l == lang).map(|(_, text)| *text).unwrap_or("")
+}
+
+fn po_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            _ => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// msgctxt carries the uuid so entries with the same source text stay separate
+fn export_po(msg: &Msg, source: usize, target: usize) -> String {
+    let mut out = String::new();
+    out.push_str("msgid \"\"\nmsgstr \"\"\n");
+    out.push_str("\"Content-Type: text/plain; charset=UTF-8\\n\"\n");
+    out.push_str(&format!("\"Language: {}\\n\"\n", lang_code(target)));
+    out.push_str(&format!("\"X-Source-Language: {}\\n\"\n", lang_code(source)));
+    for (uuid, name, content) in msg.texts() {
+        out.push_str(&format!("\n#. {name}\nmsgctxt {}\n", po_escape(&uuid)));
+        out.push_str(&format!("msgid {}\n", po_escape(text(&content, source))));
+        out.push_str(&format!("msgstr {}\n", po_escape(text(&content, target))));
+    }
+    out
+}
+
+fn xml_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn export_xliff12(msg: &Msg, source: usize, target: usize, original: &str) -> String {
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str("<xliff version=\"1.2\" xmlns=\"urn:oasis:names:tc:xliff:document:1.2\">\n");
+    out.push_str(&format!(
+        "  <file original=\"{}\" source-language=\"{}\" target-language=\"{}\" datatype=\"plaintext\">\n    <body>\n",
+        xml_escape(original), lang_code(source), lang_code(target)
+    ));
+    for (uuid, name, content) in msg.texts() {
+        out.push_str(&format!("      <trans-unit id=\"{uuid}\" resname=\"{}\" xml:space=\"preserve\">\n", xml_escape(name)));
+        out.push_str(&format!("        <source>{}</source>\n", xml_escape(text(&content, source))));
+        out.push_str(&format!("        <target>{}</target>\n", xml_escape(text(&content, target))));
+        out.push_str("      </trans-unit>\n");
+    }
+    out.push_str("    </body>\n  </file>\n</xliff>\n");
+    out
+}
+
+fn export_xliff20(msg: &Msg, source: usize, target: usize, original: &str) -> String {
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str(&format!(
+        "<xliff xmlns=\"urn:oasis:names:tc:xliff:document:2.0\" version=\"2.0\" srcLang=\"{}\" trgLang=\"{}\">\n",
+        lang_code(source), lang_code(target)
+    ));
+    out.push_str(&format!("  <file id=\"f1\" original=\"{}\">\n", xml_escape(original)));
+    for (uuid, name, content) in msg.texts() {
+        out.push_str(&format!("    <unit id=\"{uuid}\" name=\"{}\">\n      <segment>\n", xml_escape(name)));
+        out.push_str(&format!("        <source xml:space=\"preserve\">{}</source>\n", xml_escape(text(&content, source))));
+        out.push_str(&format!("        <target xml:space=\"preserve\">{}</target>\n", xml_escape(text(&content, target))));
+        out.push_str("      </segment>\n    </unit>\n");
+    }
+    out.push_str("  </file>\n</xliff>\n");
+    out
+}
+
+fn csv_escape(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+// one column per language, headed by the lang_map name
+fn export_csv(msg: &Msg) -> String {
+    let mut out = String::from("guid,name");
+    for lang in msg.languages() {
+        out.push(',');
+        out.push_str(&csv_escape(&lang_name(lang)));
+    }
+    out.push_str("\r\n");
+    for (uuid, name, content) in msg.texts() {
+        out.push_str(&uuid);
+        out.push(',');
+        out.push_str(&csv_escape(name));
+        for (_, c) in content {
+            out.push(',');
+            out.push_str(&csv_escape(c));
+        }
+        out.push_str("\r\n");
+    }
+    out
+}
+
+// key (uuid or name) -> language -> text
+pub type Translations = HashMap<String, HashMap<usize, String>>;
+
+// target is used when the file itself doesn't say which language it holds
+pub fn import(data: &str, format: Format, target: Option<usize>) -> Result<Translations> {
+    match format {
+        Format::Po => import_po(data, target),
+        Format::Xliff12 => import_xliff(data, "trans-unit", "target-language", target),
+        Format::Xliff20 => import_xliff(data, "unit", "trgLang", target),
+        Format::Csv => import_csv(data),
+    }
+}
+
+// merges into msg, returns the keys that didn't match any entry
+pub fn apply(msg: &mut Msg, translations: Translations) -> Vec<String> {
+    let mut missing = Vec::new();
+    for (key, texts) in translations {
+        let mut found = false;
+        for (lang, text) in texts {
+            found |= msg.set_text(&key, lang, text);
+        }
+        if !found {
+            missing.push(key);
+        }
+    }
+    missing
+}
+
+fn po_unescape(s: &str) -> Result<String> {
+    let s = s.trim();
+    let inner = s.strip_prefix('"').and_then(|s| s.strip_suffix('"'))
+        .ok_or_else(|| format!("Expected quoted string in po, got {s}"))?;
+    let mut out = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some(c) => out.push(c),
+            None => return Err(format!("Dangling escape in po string {s}").into()),
+        }
+    }
+    Ok(out)
+}
+
+fn import_po(data: &str, target: Option<usize>) -> Result<Translations> {
+    #[derive(Default)]
+    struct PoEntry {
+        ctxt: Option<String>,
+        id: String,
+        str: String,
+        comment: Option<String>,
+    }
+    let mut entries: Vec<PoEntry> = Vec::new();
+    let mut cur = PoEntry::default();
+    let mut started = false;
+    let mut field: Option<&str> = None;
+    for line in data.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue
+        }
+        // a new msgctxt/msgid after a msgstr starts a new entry
+        let keyword = ["msgctxt", "msgid", "msgstr"].into_iter()
+            .find(|k| line.starts_with(k) && line[k.len()..].starts_with(' '));
+        if (keyword == Some("msgctxt") || keyword == Some("msgid")) && field == Some("msgstr")
+            || line.starts_with('#') && field == Some("msgstr") {
+            entries.push(std::mem::take(&mut cur));
+            started = false;
+            field = None;
+        }
+        if let Some(comment) = line.strip_prefix("#.") {
+            cur.comment = Some(comment.trim().to_string());
+            continue
+        }
+        if line.starts_with('#') {
+            continue
+        }
+        let value = match keyword {
+            Some(k) => {
+                field = Some(k);
+                started = true;
+                po_unescape(&line[k.len()..])?
+            },
+            None if line.starts_with('"') && started => po_unescape(line)?,
+            None => return Err(format!("Unexpected po line {line}").into()),
+        };
+        match field {
+            Some("msgctxt") => cur.ctxt.get_or_insert_with(String::new).push_str(&value),
+            Some("msgid") => cur.id.push_str(&value),
+            Some("msgstr") => cur.str.push_str(&value),
+            _ => unreachable!(),
+        }
+    }
+    if started {
+        entries.push(cur);
+    }
+
+    let mut lang = target;
+    let mut translations = Translations::new();
+    for entry in entries {
+        if entry.id.is_empty() && entry.ctxt.is_none() {
+            // header
+            if let Some(code) = entry.str.lines().find_map(|l| l.strip_prefix("Language:")) {
+                lang = lang.or(lang_from_str(code.trim()));
+            }
+            continue
+        }
+        // untranslated entries keep whatever is already in the file
+        if entry.str.is_empty() {
+            continue
+        }
+        let key = entry.ctxt.or(entry.comment)
+            .ok_or_else(|| format!("po entry {:?} has no msgctxt to match it to a message", entry.id))?;
+        let lang = lang.ok_or("po file has no Language header, pass a target language")?;
+        translations.entry(key).or_default().insert(lang, entry.str);
+    }
+    Ok(translations)
+}
+
+fn xml_unescape(s: &str) -> Result<String> {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find('&') {
+        out.push_str(&rest[..i]);
+        let end = rest[i..].find(';').ok_or_else(|| format!("Unterminated xml entity in {s}"))?;
+        let entity = &rest[i + 1..i + end];
+        match entity {
+            "amp" => out.push('&'),
+            "lt" => out.push('<'),
+            "gt" => out.push('>'),
+            "quot" => out.push('"'),
+            "apos" => out.push('\''),
+            _ => {
+                let code = if let Some(hex) = entity.strip_prefix("#x") {
+                    u32::from_str_radix(hex, 16).ok()
+                } else if let Some(dec) = entity.strip_prefix('#') {
+                    dec.parse().ok()
+                } else {
+                    None
+                };
+                let c = code.and_then(char::from_u32).ok_or_else(|| format!("Unknown xml entity &{entity};"))?;
+                out.push(c);
+            }
+        }
+        rest = &rest[i + end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn xml_attr(tag: &str, name: &str) -> Option<String> {
+    for quote in ['"', '\''] {
+        let pat = format!(" {name}={quote}");
+        if let Some(start) = tag.find(&pat) {
+            let value = &tag[start + pat.len()..];
+            return value.find(quote).and_then(|end| xml_unescape(&value[..end]).ok())
+        }
+    }
+    None
+}
+
+// returns (open tag, inner text) of the first <name> element in s, and the rest after it
+fn xml_element<'a>(s: &'a str, name: &str) -> Option<(&'a str, &'a str, &'a str)> {
+    let open = format!("<{name}");
+    let mut from = 0;
+    let start = loop {
+        let i = from + s[from..].find(&open)?;
+        // don't match <unit> against <units> or <target> against <target-language>
+        match s[i + open.len()..].chars().next() {
+            Some(c) if c == '>' || c == '/' || c.is_whitespace() => break i,
+            _ => from = i + open.len(),
+        }
+    };
+    let tag_end = start + s[start..].find('>')?;
+    let tag = &s[start..tag_end];
+    if tag.ends_with('/') {
+        return Some((tag, "", &s[tag_end + 1..]))
+    }
+    let close = format!("</{name}>");
+    let inner_end = tag_end + 1 + s[tag_end + 1..].find(&close)?;
+    Some((tag, &s[tag_end + 1..inner_end], &s[inner_end + close.len()..]))
+}
+
+// only reads the bits we write, inline markup inside targets is kept as raw text
+fn import_xliff(data: &str, unit: &str, lang_attr: &str, target: Option<usize>) -> Result<Translations> {
+    let lang = match target {
+        Some(lang) => lang,
+        None => {
+            let code = xml_element(data, "xliff").and_then(|(tag, _, _)| xml_attr(tag, lang_attr))
+                .or_else(|| xml_element(data, "file").and_then(|(tag, _, _)| xml_attr(tag, lang_attr)))
+                .ok_or_else(|| format!("xliff has no {lang_attr}, pass a target language"))?;
+            lang_from_str(&code).ok_or_else(|| format!("Unknown xliff language {code}"))?
+        }
+    };
+    let mut translations = Translations::new();
+    let mut rest = data;
+    while let Some((tag, inner, next)) = xml_element(rest, unit) {
+        rest = next;
+        let key = xml_attr(tag, "id")
+            .or_else(|| xml_attr(tag, "resname"))
+            .or_else(|| xml_attr(tag, "name"))
+            .ok_or_else(|| format!("xliff {unit} without id: {tag}"))?;
+        let text = match xml_element(inner, "target") {
+            Some((_, text, _)) => xml_unescape(text)?,
+            None => continue,
+        };
+        if text.is_empty() {
+            continue
+        }
+        translations.entry(key).or_default().insert(lang, text);
+    }
+    Ok(translations)
+}
+
+fn csv_records(data: &str) -> Result<Vec<Vec<String>>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = data.chars().peekable();
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                },
+                '"' => quoted = false,
+                _ => field.push(c),
+            }
+            continue
+        }
+        match c {
+            '"' if field.is_empty() => quoted = true,
+            ',' => record.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => (),
+            '\n' => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            },
+            _ => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("Unterminated quoted field in csv".into())
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    Ok(records)
+}
+
+fn import_csv(data: &str) -> Result<Translations> {
+    let mut records = csv_records(data.trim_start_matches('\u{feff}'))?.into_iter();
+    let header = records.next().ok_or("Empty csv")?;
+    if header.len() < 2 || header[0] != "guid" || header[1] != "name" {
+        return Err("csv must start with guid,name columns".into())
+    }
+    let langs = header[2..].iter()
+        .map(|name| lang_from_str(name).ok_or_else(|| format!("Unknown csv language column {name}")))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    let mut translations = Translations::new();
+    for record in records {
+        let key = match (record.first(), record.get(1)) {
+            (Some(guid), _) if !guid.is_empty() => guid.clone(),
+            (_, Some(name)) if !name.is_empty() => name.clone(),
+            _ => continue,
+        };
+        let texts = translations.entry(key).or_default();
+        for (lang, text) in langs.iter().zip(record.into_iter().skip(2)) {
+            if !text.is_empty() {
+                texts.insert(*lang, text);
+            }
+        }
+    }
+    Ok(translations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXTS: [(&str, &str, &str); 3] = [
+        ("00112233-4455-6677-8899-aabbccddeeff",
+            "Say \"hi\", then <COL RED>run</COL> & <PL>",
+            "Dis \"salut\", puis <COL RED>cours</COL> & <PL>"),
+        ("ffeeddcc-bbaa-9988-7766-554433221100",
+            "line one\r\nline two\nand a\ttab, 'quoted' {0}",
+            "ligne un\r\nligne deux\net une\ttab, 'cité' {0}"),
+        ("0f0f0f0f-0000-1111-2222-333344445555",
+            "<REF Item_000>\\n is not a newline",
+            "<REF Item_000>\\n n'est pas un retour"),
+    ];
+
+    fn sample() -> Msg {
+        let msgs: serde_json::Map<String, serde_json::Value> = TEXTS.iter().enumerate().map(|(i, (uuid, en, fr))| {
+            (uuid.to_string(), serde_json::json!({
+                "name": format!("Test_{i:02}"),
+                "hash": i,
+                "attributes": [],
+                "content": { "English": en, "French": fr },
+            }))
+        }).collect();
+        let json = serde_json::json!({ "languages": [1, 2], "msgs": msgs, "attributes": [] });
+        Msg::from_json(json.to_string().as_bytes(), 23).unwrap()
+    }
+
+    fn expected(langs: &[usize]) -> Translations {
+        TEXTS.iter().map(|(uuid, en, fr)| {
+            let texts = langs.iter().map(|&lang| (lang, if lang == 1 { en } else { fr }.to_string())).collect();
+            (uuid.to_string(), texts)
+        }).collect()
+    }
+
+    // the target language comes from the exported file itself
+    #[test]
+    fn export_import_round_trip() {
+        let msg = sample();
+        for format in [Format::Po, Format::Xliff12, Format::Xliff20] {
+            let data = export(&msg, format, 1, 2, "test.msg.23");
+            let translations = import(&data, format, None).unwrap_or_else(|e| panic!("{format:?}: {e}"));
+            assert_eq!(translations, expected(&[2]), "{format:?}\n{data}");
+        }
+        let data = export(&msg, Format::Csv, 1, 2, "test.msg.23");
+        assert_eq!(import(&data, Format::Csv, None).unwrap(), expected(&[1, 2]), "{data}");
+    }
+
+    #[test]
+    fn apply_restores_texts() {
+        let original = sample();
+        for format in [Format::Po, Format::Xliff12, Format::Xliff20, Format::Csv] {
+            let data = export(&original, format, 1, 2, "test.msg.23");
+            let mut msg = sample();
+            for (uuid, _, _) in TEXTS {
+                msg.set_text(uuid, 2, String::new());
+            }
+            let missing = apply(&mut msg, import(&data, format, None).unwrap());
+            assert!(missing.is_empty(), "{format:?}: {missing:?}");
+            assert_eq!(msg.texts().collect::<Vec<_>>(), original.texts().collect::<Vec<_>>(), "{format:?}");
+        }
+    }
+}